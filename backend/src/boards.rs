@@ -0,0 +1,295 @@
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    fs,
+    hash::{BuildHasher, Hasher},
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use shared::{
+    display_name, is_valid_board_id, participant_color, Cursor, Envelope, Event, Hello, Operation,
+    Participant,
+};
+use tokio::sync::broadcast;
+
+use crate::{state::BoardState, storage::Storage};
+
+const BOARDS_DIR: &str = "boards";
+
+/// Number of batches of operations a slow subscriber may fall behind before it is disconnected.
+const EVENT_BUFFER: usize = 256;
+
+pub struct Board {
+    pub state: BoardState,
+    /// Clients that said hello recently, by session.
+    presence: BTreeMap<u64, Participant>,
+    storage: Storage,
+    events: broadcast::Sender<String>,
+}
+
+impl Board {
+    fn open(dir: PathBuf) -> io::Result<Self> {
+        let (storage, state) = Storage::open(dir)?;
+
+        Ok(Self {
+            state,
+            presence: BTreeMap::new(),
+            storage,
+            events: broadcast::channel(EVENT_BUFFER).0,
+        })
+    }
+
+    /// Writes `operations` to the journal, applies them to the board and sends them to all subscribers.
+    pub fn commit(&mut self, operations: Vec<Operation>) -> io::Result<()> {
+        self.storage.record(&operations)?;
+
+        let event = serde_json::to_string(&Envelope::new(operations.clone()))?;
+
+        for operation in operations {
+            self.state.apply(operation);
+        }
+
+        // Sending only fails if nobody is subscribed.
+        self.events.send(event).ok();
+
+        self.storage.compact_if_needed(&self.state)
+    }
+
+    /// Marks the client of `hello` as being on the board.
+    ///
+    /// New sessions get a color, which they keep until they are forgotten.
+    pub fn greet(&mut self, hello: Hello) -> Participant {
+        let session = hello.session.unwrap_or_else(|| new_session(&self.presence));
+
+        // The first color nobody on the board has.
+        let color = match self.presence.get(&session) {
+            Some(participant) => participant.color,
+            None => (0..)
+                .map(participant_color)
+                .find(|color| {
+                    self.presence
+                        .values()
+                        .all(|participant| participant.color != *color)
+                })
+                .unwrap(),
+        };
+
+        let participant = Participant {
+            session,
+            name: display_name(&hello.name),
+            color,
+            last_seen: now_millis(),
+        };
+
+        self.presence.insert(session, participant.clone());
+
+        participant
+    }
+
+    /// Everyone on the board, ordered by session.
+    pub fn participants(&self) -> Vec<Participant> {
+        self.presence.values().cloned().collect()
+    }
+
+    /// Removes clients that have not said hello for longer than `timeout`.
+    pub fn forget_idle(&mut self, timeout: Duration) {
+        let now = now_millis();
+
+        self.presence.retain(|_, participant| {
+            now.saturating_sub(participant.last_seen) < timeout.as_millis() as u64
+        });
+    }
+
+    /// Sends `cursor` to all subscribers, if it belongs to someone on the board.
+    pub fn publish_cursor(&self, cursor: Cursor) {
+        if !self.presence.contains_key(&cursor.session) {
+            return;
+        }
+
+        let event = serde_json::to_string(&Event::Cursor { cursor }).unwrap();
+
+        self.events.send(event).ok();
+    }
+
+    /// Receives every batch of operations committed from now on and every cursor as serialized [`Event`].
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.events.subscribe()
+    }
+}
+
+fn new_session(presence: &BTreeMap<u64, Participant>) -> u64 {
+    loop {
+        // Every `RandomState` is seeded differently, which is random enough for ids that are not secrets.
+        let session = RandomState::new().build_hasher().finish();
+
+        if !presence.contains_key(&session) {
+            return session;
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// All boards of this server, each persisted in its own directory below the data directory.
+pub struct Boards {
+    dir: PathBuf,
+    boards: BTreeMap<String, Arc<Mutex<Board>>>,
+}
+
+impl Boards {
+    /// Loads every board found in `data_dir`.
+    pub fn load(data_dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = data_dir.into().join(BOARDS_DIR);
+
+        fs::create_dir_all(&dir)?;
+
+        let mut boards = Self {
+            dir,
+            boards: BTreeMap::new(),
+        };
+
+        for entry in fs::read_dir(&boards.dir)? {
+            let entry = entry?;
+
+            let Some(id) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+
+            if !entry.file_type()?.is_dir() || !is_valid_board_id(&id) {
+                log::warn!("Skipping unexpected entry {:?}", entry.path());
+                continue;
+            }
+
+            let board = Board::open(entry.path())?;
+
+            boards.boards.insert(id, Arc::new(Mutex::new(board)));
+        }
+
+        log::info!("Loaded {} boards", boards.boards.len());
+
+        Ok(boards)
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.boards.keys().cloned().collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Mutex<Board>>> {
+        self.boards.get(id).cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Mutex<Board>>> {
+        self.boards.values()
+    }
+
+    /// Creates a new empty board. Returns `None` if it already exists.
+    pub fn create(&mut self, id: &str) -> io::Result<Option<Arc<Mutex<Board>>>> {
+        if self.boards.contains_key(id) {
+            return Ok(None);
+        }
+
+        self.get_or_create(id).map(Some)
+    }
+
+    pub fn get_or_create(&mut self, id: &str) -> io::Result<Arc<Mutex<Board>>> {
+        if let Some(board) = self.boards.get(id) {
+            return Ok(board.clone());
+        }
+
+        log::info!("Creating board {:?}", id);
+
+        let board = Arc::new(Mutex::new(Board::open(self.dir.join(id))?));
+
+        self.boards.insert(id.to_string(), board.clone());
+
+        Ok(board)
+    }
+
+    /// Removes the board and its files. Returns `false` if it did not exist.
+    ///
+    /// Websockets only hold the board weakly, their events end once the board is gone, see
+    /// [`crate::websocket::run`].
+    pub fn delete(&mut self, id: &str) -> io::Result<bool> {
+        if self.boards.remove(id).is_none() {
+            return Ok(false);
+        }
+
+        log::info!("Deleting board {:?}", id);
+
+        fs::remove_dir_all(self.dir.join(id))?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::*;
+
+    fn hello(session: Option<u64>) -> Hello {
+        Hello {
+            session,
+            name: "Ada".to_string(),
+        }
+    }
+
+    #[test]
+    fn participants_get_colors_nobody_else_has() {
+        let dir = std::env::temp_dir().join(format!("boards-{}-greet", std::process::id()));
+
+        fs::remove_dir_all(&dir).ok();
+
+        let mut board = Board::open(dir.clone()).unwrap();
+
+        let first = board.greet(hello(None));
+        let second = board.greet(hello(None));
+
+        assert_ne!(first.session, second.session);
+        assert_eq!(first.color, participant_color(0));
+        assert_eq!(second.color, participant_color(1));
+
+        // Coming back keeps the color.
+        assert_eq!(board.greet(hello(Some(first.session))).color, first.color);
+
+        board.presence.remove(&first.session);
+
+        // The color of someone who left is given to the next one.
+        let third = board.greet(hello(None));
+
+        assert_eq!(third.color, first.color);
+        assert_eq!(board.participants().len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deleting_a_board_ends_its_events() {
+        let dir = std::env::temp_dir().join(format!("boards-{}-delete", std::process::id()));
+
+        fs::remove_dir_all(&dir).ok();
+
+        let mut boards = Boards::load(&dir).unwrap();
+
+        let board = boards.create("doomed").unwrap().unwrap();
+        let mut events = board.lock().unwrap().subscribe();
+
+        // What a websocket holds on to.
+        let weak = Arc::downgrade(&board);
+        drop(board);
+
+        assert!(boards.delete("doomed").unwrap());
+        assert!(weak.upgrade().is_none());
+        assert_eq!(events.try_recv(), Err(TryRecvError::Closed));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+use std::{fmt::Display, fs, net::ToSocketAddrs, path::PathBuf, str::FromStr, time::Duration};
+
+use log::LevelFilter;
+use serde::Deserialize;
+
+/// Options that can be given as `--<name> <value>` or `WEBPAINT_<NAME>`, with what they do.
+const OPTIONS: &[(&str, &str)] = &[
+    ("config", "TOML file with any of the options below, names use `_` instead of `-`"),
+    ("bind", "Address to listen on [default: 0.0.0.0]"),
+    ("port", "Port to listen on [default: 8432]"),
+    (
+        "cors-origins",
+        "Comma separated origins allowed to call the backend from a browser, `*` allows any [default: *]",
+    ),
+    ("data-dir", "Directory the boards are stored in [default: data]"),
+    (
+        "presence-timeout-secs",
+        "Seconds until a client that was not seen stops counting as connected [default: 30]",
+    ),
+    ("max-payload-bytes", "Largest JSON body accepted [default: 2097152]"),
+    ("max-operations", "Most operations accepted in one request [default: 10000]"),
+    ("log-level", "One of off, error, warn, info, debug and trace, overridden by RUST_LOG [default: info]"),
+];
+
+/// How the server runs, see [`Config::load`].
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    pub port: u16,
+    /// Origins allowed by CORS, `*` allows any and must then be the only one.
+    pub cors_origins: Vec<String>,
+    pub data_dir: PathBuf,
+    pub presence_timeout_secs: u64,
+    pub max_payload_bytes: usize,
+    pub max_operations: usize,
+    pub log_level: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".to_string(),
+            port: 8432,
+            cors_origins: vec!["*".to_string()],
+            data_dir: PathBuf::from("data"),
+            presence_timeout_secs: 30,
+            max_payload_bytes: 2 * 1024 * 1024,
+            max_operations: 10_000,
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file, then `WEBPAINT_*` variables, then command line flags, later ones winning.
+    ///
+    /// The config file is given with `--config` or `WEBPAINT_CONFIG`.
+    pub fn load() -> Result<Self, String> {
+        let from_env: Vec<(String, String)> = OPTIONS
+            .iter()
+            .filter_map(|(name, _)| {
+                let value = std::env::var(env_name(name)).ok()?;
+                Some((name.to_string(), value))
+            })
+            .collect();
+
+        let from_args = parse_args(std::env::args().skip(1))?;
+
+        Self::from_options(&from_env, &from_args)
+    }
+
+    /// The config for options from the environment and the command line, in that order.
+    fn from_options(
+        from_env: &[(String, String)],
+        from_args: &[(String, String)],
+    ) -> Result<Self, String> {
+        let options = || from_env.iter().chain(from_args);
+
+        let mut config = match options().filter(|(name, _)| name == "config").last() {
+            Some((_, path)) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        for (name, value) in options() {
+            config.set(name, value)?;
+        }
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config file {:?}: {}", path, e))?;
+
+        toml::from_str(&text).map_err(|e| format!("Invalid config file {:?}: {}", path, e))
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "config" => {}
+            "bind" => self.bind = value.to_string(),
+            "port" => self.port = parse(name, value)?,
+            "cors-origins" => {
+                self.cors_origins = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|origin| !origin.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "data-dir" => self.data_dir = PathBuf::from(value),
+            "presence-timeout-secs" => self.presence_timeout_secs = parse(name, value)?,
+            "max-payload-bytes" => self.max_payload_bytes = parse(name, value)?,
+            "max-operations" => self.max_operations = parse(name, value)?,
+            "log-level" => self.log_level = value.to_string(),
+            _ => return Err(format!("Unknown option --{}, see --help", name)),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("port must not be 0".to_string());
+        }
+
+        (self.bind.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| format!("Cannot listen on {:?}: {}", self.bind, e))?;
+
+        // Without any origin every browser would be turned away.
+        if self.cors_origins.is_empty() {
+            return Err("cors-origins must list at least one origin, or `*` for any".to_string());
+        }
+
+        if self.cors_origins.len() > 1 && self.cors_origins.iter().any(|origin| origin == "*") {
+            return Err("cors-origins cannot combine `*` with other origins".to_string());
+        }
+
+        for origin in self.cors_origins.iter().filter(|origin| *origin != "*") {
+            if !is_valid_origin(origin) {
+                return Err(format!(
+                    "Invalid CORS origin {:?}, expected something like https://example.com",
+                    origin
+                ));
+            }
+        }
+
+        if self.presence_timeout_secs == 0 {
+            return Err("presence-timeout-secs must be at least 1".to_string());
+        }
+
+        if self.max_payload_bytes == 0 {
+            return Err("max-payload-bytes must be at least 1".to_string());
+        }
+
+        if self.max_operations == 0 {
+            return Err("max-operations must be at least 1".to_string());
+        }
+
+        self.log_level_filter()?;
+
+        Ok(())
+    }
+
+    pub fn log_level_filter(&self) -> Result<LevelFilter, String> {
+        self.log_level
+            .parse()
+            .map_err(|_| format!("Invalid log-level {:?}", self.log_level))
+    }
+
+    pub fn presence_timeout(&self) -> Duration {
+        Duration::from_secs(self.presence_timeout_secs)
+    }
+
+    pub fn allows_any_origin(&self) -> bool {
+        self.cors_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+/// Reads `--name value` and `--name=value` pairs, printing the usage for `--help`.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Vec<(String, String)>, String> {
+    let mut args = args.peekable();
+    let mut options = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            print_usage();
+            std::process::exit(0);
+        }
+
+        let Some(option) = arg.strip_prefix("--") else {
+            return Err(format!("Unexpected argument {:?}, see --help", arg));
+        };
+
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for --{}", option))?;
+
+                (option.to_string(), value)
+            }
+        };
+
+        if !OPTIONS.iter().any(|(known, _)| *known == name) {
+            return Err(format!("Unknown option --{}, see --help", name));
+        }
+
+        options.push((name, value));
+    }
+
+    Ok(options)
+}
+
+fn print_usage() {
+    println!("Usage: backend [--<option> <value>]...\n\nOptions:");
+
+    for (name, description) in OPTIONS {
+        println!("  --{:<24}{}", name, description);
+        println!("  {:<26}env: {}", "", env_name(name));
+    }
+}
+
+fn env_name(option: &str) -> String {
+    format!("WEBPAINT_{}", option.to_uppercase().replace('-', "_"))
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("Invalid value {:?} for {}: {}", value, name, e))
+}
+
+/// A scheme and host with an optional port, which is how browsers send the `Origin` header.
+fn is_valid_origin(origin: &str) -> bool {
+    let Some(host) = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"))
+    else {
+        return false;
+    };
+
+    !host.is_empty() && !host.contains(['/', '?', '#', ' '])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn options(options: &[(&str, &str)]) -> Vec<(String, String)> {
+        options
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn args_are_read_in_both_forms() {
+        assert_eq!(
+            parse_args(args(&["--port", "9000", "--bind=127.0.0.1"])).unwrap(),
+            options(&[("port", "9000"), ("bind", "127.0.0.1")])
+        );
+
+        assert_eq!(
+            parse_args(args(&["--port"])).unwrap_err(),
+            "Missing value for --port"
+        );
+        assert_eq!(
+            parse_args(args(&["--colour", "red"])).unwrap_err(),
+            "Unknown option --colour, see --help"
+        );
+        assert_eq!(
+            parse_args(args(&["port"])).unwrap_err(),
+            "Unexpected argument \"port\", see --help"
+        );
+    }
+
+    #[test]
+    fn args_win_over_the_environment_and_the_config_file() {
+        let path = std::env::temp_dir().join(format!("config-{}.toml", std::process::id()));
+
+        fs::write(
+            &path,
+            "port = 9000\nmax_operations = 5\nlog_level = \"debug\"\n",
+        )
+        .unwrap();
+
+        let config = Config::from_options(
+            &options(&[("config", path.to_str().unwrap()), ("port", "9001")]),
+            &options(&[
+                ("port", "9002"),
+                ("cors-origins", "http://a.test, https://b.test"),
+            ]),
+        )
+        .unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.port, 9002);
+        assert_eq!(config.max_operations, 5);
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.cors_origins, ["http://a.test", "https://b.test"]);
+        assert!(!config.allows_any_origin());
+
+        assert!(
+            Config::from_options(&options(&[("config", "missing.toml")]), &[])
+                .unwrap_err()
+                .starts_with("Cannot read config file \"missing.toml\"")
+        );
+        assert_eq!(
+            Config::from_options(&[], &options(&[("port", "high")])).unwrap_err(),
+            "Invalid value \"high\" for port: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn invalid_configs_are_explained() {
+        let invalid = |options: &[(&str, &str)]| {
+            let mut config = Config::default();
+
+            for (name, value) in options {
+                config.set(name, value).unwrap();
+            }
+
+            config.validate().unwrap_err()
+        };
+
+        assert!(Config::default().validate().is_ok());
+
+        assert_eq!(invalid(&[("port", "0")]), "port must not be 0");
+        assert_eq!(
+            invalid(&[("cors-origins", "*,https://a.test")]),
+            "cors-origins cannot combine `*` with other origins"
+        );
+        assert_eq!(
+            invalid(&[("cors-origins", " , ")]),
+            "cors-origins must list at least one origin, or `*` for any"
+        );
+        assert_eq!(
+            toml::from_str::<Config>("cors_origins = []")
+                .unwrap()
+                .validate()
+                .unwrap_err(),
+            "cors-origins must list at least one origin, or `*` for any"
+        );
+        assert_eq!(
+            invalid(&[("cors-origins", "a.test")]),
+            "Invalid CORS origin \"a.test\", expected something like https://example.com"
+        );
+        assert_eq!(
+            invalid(&[("presence-timeout-secs", "0")]),
+            "presence-timeout-secs must be at least 1"
+        );
+        assert_eq!(
+            invalid(&[("max-operations", "0")]),
+            "max-operations must be at least 1"
+        );
+        assert_eq!(
+            invalid(&[("log-level", "loud")]),
+            "Invalid log-level \"loud\""
+        );
+    }
+}
@@ -0,0 +1,15 @@
+/// The background images the frontend offers, shared through `assets/images.in`.
+const IMAGES: &[(&str, &[u8])] =
+    &include!(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/images.in"));
+
+/// The PNG of a background by the name the frontend shows for it, the file name without extension.
+pub fn background(name: &str) -> Option<&'static [u8]> {
+    IMAGES
+        .iter()
+        .find(|(file_path, _)| {
+            let file_name = file_path.split('/').last().unwrap_or_default();
+
+            file_name.trim_end_matches(".png") == name
+        })
+        .map(|(_, data)| *data)
+}
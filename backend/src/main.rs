@@ -1,87 +1,380 @@
-use std::{
-    collections::BTreeMap,
-    sync::{Arc, LazyLock, Mutex},
-    time::Instant,
-};
+mod boards;
+mod config;
+mod images;
+mod render;
+mod state;
+mod storage;
+mod websocket;
+
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 
 use actix_cors::Cors;
-use actix_web::{get, post, web, App, HttpRequest, HttpServer, Responder};
-use shared::Lines;
+use actix_web::{
+    delete, error, get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use boards::{Board, Boards};
+use config::Config;
+use serde::Deserialize;
+use shared::{
+    is_valid_board_id, Delta, Envelope, Hello, Lines, Operation, Participant, Presence,
+    PROTOCOL_VERSION,
+};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+static BOARDS: OnceLock<Mutex<Boards>> = OnceLock::new();
+
+fn boards() -> MutexGuard<'static, Boards> {
+    BOARDS
+        .get()
+        .expect("boards are loaded before the server starts")
+        .lock()
+        .unwrap()
+}
+
+fn config() -> &'static Config {
+    CONFIG
+        .get()
+        .expect("config is loaded before the server starts")
+}
+
+fn board_id(id: web::Path<String>) -> actix_web::Result<String> {
+    let id = id.into_inner();
+
+    if !is_valid_board_id(&id) {
+        return Err(error::ErrorBadRequest(format!(
+            "Invalid board id: {:?}",
+            id
+        )));
+    }
+
+    Ok(id)
+}
+
+fn persist_error(e: std::io::Error) -> actix_web::Error {
+    log::error!("Failed to persist change: {}", e);
+    error::ErrorInternalServerError(e)
+}
+
+/// Applies `operations` to the board, creating the board on first use.
+fn commit(id: &str, operations: Vec<Operation>) -> actix_web::Result<&'static str> {
+    let board = boards().get_or_create(id).map_err(persist_error)?;
 
-static LINES: LazyLock<Arc<Mutex<Lines>>> =
-    LazyLock::new(|| Arc::new(Mutex::new(Lines::default())));
+    board
+        .lock()
+        .unwrap()
+        .commit(operations)
+        .map_err(persist_error)?;
 
-static CONNECTIONS: LazyLock<Arc<Mutex<BTreeMap<String, Instant>>>> =
-    LazyLock::new(|| Arc::new(Mutex::new(BTreeMap::new())));
+    Ok("ok")
+}
+
+/// Lines of the board, empty if it does not exist yet.
+fn board_lines(id: &str) -> Lines {
+    match boards().get(id) {
+        Some(board) => board.lock().unwrap().state.lines.clone(),
+        None => Lines::default(),
+    }
+}
+
+/// The board `id`, a 404 unless it was created, see [`create_board`].
+fn existing_board(id: &str) -> actix_web::Result<Arc<Mutex<Board>>> {
+    boards()
+        .get(id)
+        .ok_or_else(|| error::ErrorNotFound(format!("Board {:?} does not exist", id)))
+}
+
+fn connection_key(req: &HttpRequest) -> String {
+    req.connection_info()
+        .realip_remote_addr()
+        .unwrap_or_default()
+        .to_string()
+}
 
 #[get("/hello/{name}")]
 async fn greet(name: web::Path<String>) -> impl Responder {
     format!("Hello {}!", name)
 }
 
-#[get("/lines")]
-async fn get_lines(req: HttpRequest) -> impl Responder {
-    let ip = req
-        .connection_info()
-        .realip_remote_addr()
-        .unwrap_or_default()
-        .to_string();
+#[get("/boards")]
+async fn list_boards() -> impl Responder {
+    web::Json(boards().ids())
+}
+
+#[post("/boards/{id}")]
+async fn create_board(id: web::Path<String>) -> actix_web::Result<HttpResponse> {
+    let id = board_id(id)?;
+
+    match boards().create(&id).map_err(persist_error)? {
+        Some(_) => Ok(HttpResponse::Created().body("ok")),
+        None => Ok(HttpResponse::Conflict().body(format!("Board {:?} already exists", id))),
+    }
+}
 
-    CONNECTIONS.lock().unwrap().insert(ip, Instant::now());
+#[delete("/boards/{id}")]
+async fn delete_board(id: web::Path<String>) -> actix_web::Result<HttpResponse> {
+    let id = board_id(id)?;
 
-    LINES.lock().unwrap().to_string()
+    match boards().delete(&id).map_err(persist_error)? {
+        true => Ok(HttpResponse::Ok().body("ok")),
+        false => Ok(HttpResponse::NotFound().body(format!("Board {:?} does not exist", id))),
+    }
 }
 
-#[post("/lines")]
-async fn post_lines(lines: web::Json<Lines>) -> impl Responder {
-    let lines = lines.into_inner();
-    LINES.lock().unwrap().update_from_other(lines);
-    "ok"
+#[derive(Deserialize)]
+struct LinesQuery {
+    /// Revision the client already has, everything is returned if missing.
+    #[serde(default)]
+    since: u64,
 }
 
-#[post("/remove_lines")]
-async fn remove_lines(ids: web::Json<Vec<u64>>) -> impl Responder {
-    let mut lines = LINES.lock().unwrap();
-    for id in ids.into_inner() {
-        lines.remove(&id);
+#[get("/boards/{id}/lines")]
+async fn get_lines(
+    id: web::Path<String>,
+    query: web::Query<LinesQuery>,
+) -> actix_web::Result<web::Json<Delta>> {
+    let id = board_id(id)?;
+
+    let Some(board) = boards().get(&id) else {
+        return Ok(web::Json(Delta {
+            full: true,
+            ..Default::default()
+        }));
+    };
+
+    let board = board.lock().unwrap();
+
+    Ok(web::Json(board.state.delta_since(query.since)))
+}
+
+#[post("/boards/{id}/operations")]
+async fn post_operations(
+    id: web::Path<String>,
+    envelope: web::Json<Envelope>,
+) -> actix_web::Result<&'static str> {
+    let id = board_id(id)?;
+
+    let envelope = envelope.into_inner();
+
+    if !envelope.is_supported() {
+        return Err(error::ErrorBadRequest(format!(
+            "Unsupported protocol version {}, expected {}",
+            envelope.version, PROTOCOL_VERSION
+        )));
+    }
+
+    if envelope.operations.len() > config().max_operations {
+        return Err(error::ErrorPayloadTooLarge(format!(
+            "At most {} operations are accepted per request",
+            config().max_operations
+        )));
     }
 
-    "ok"
+    commit(&id, envelope.operations)
 }
 
-#[post("/clear")]
-async fn clear_lines() -> impl Responder {
-    LINES.lock().unwrap().clear();
-    "ok"
+#[get("/boards/{id}/events")]
+async fn board_events(
+    req: HttpRequest,
+    body: web::Payload,
+    id: web::Path<String>,
+) -> actix_web::Result<HttpResponse> {
+    let id = board_id(id)?;
+
+    let board = existing_board(&id)?;
+
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+
+    let connection = connection_key(&req);
+
+    let events = board.lock().unwrap().subscribe();
+
+    actix_web::rt::spawn(websocket::run(
+        Arc::downgrade(&board),
+        connection,
+        events,
+        session,
+        messages,
+    ));
+
+    Ok(response)
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// Name of the background image, the export has none if it is missing.
+    background: Option<String>,
+    /// Width in pixels of raster exports, the size of the background by default.
+    width: Option<u32>,
+}
+
+impl ExportQuery {
+    fn background(&self) -> actix_web::Result<Option<&'static [u8]>> {
+        let Some(name) = &self.background else {
+            return Ok(None);
+        };
+
+        match images::background(name) {
+            Some(png) => Ok(Some(png)),
+            None => Err(error::ErrorBadRequest(format!(
+                "Unknown background: {:?}",
+                name
+            ))),
+        }
+    }
+}
+
+#[get("/boards/{id}/export.svg")]
+async fn export_svg(
+    id: web::Path<String>,
+    query: web::Query<ExportQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let id = board_id(id)?;
+
+    let background = query.background()?;
+
+    let svg = shared::export_svg(&board_lines(&id), background);
+
+    Ok(HttpResponse::Ok().content_type("image/svg+xml").body(svg))
+}
+
+#[get("/boards/{id}/export.png")]
+async fn export_png(
+    id: web::Path<String>,
+    query: web::Query<ExportQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let id = board_id(id)?;
+
+    let background = query.background()?;
+
+    let width = query
+        .width
+        .unwrap_or_else(|| shared::export_size(background).x as u32);
+
+    if !(1..=render::MAX_WIDTH).contains(&width) {
+        return Err(error::ErrorBadRequest(format!(
+            "Width must be between 1 and {}",
+            render::MAX_WIDTH
+        )));
+    }
+
+    let lines = board_lines(&id);
+
+    // Rendering large images takes a while, so it must not block the server.
+    let png = web::block(move || render::render_png(&lines, background, width))
+        .await?
+        .map_err(|e| {
+            log::error!("Failed to render board {:?}: {}", id, e);
+            error::ErrorInternalServerError(e)
+        })?;
+
+    Ok(HttpResponse::Ok().content_type("image/png").body(png))
+}
+
+#[get("/boards/{id}/presence")]
+async fn get_presence(id: web::Path<String>) -> actix_web::Result<web::Json<Vec<Participant>>> {
+    let id = board_id(id)?;
+
+    let participants = match boards().get(&id) {
+        Some(board) => board.lock().unwrap().participants(),
+        None => Vec::new(),
+    };
+
+    Ok(web::Json(participants))
 }
 
-#[get("/num_connections")]
-async fn num_connections() -> impl Responder {
-    CONNECTIONS.lock().unwrap().len().to_string()
+/// Keeps the client on the list of who is on the board, clients send this regularly.
+#[post("/boards/{id}/presence")]
+async fn post_presence(
+    id: web::Path<String>,
+    hello: web::Json<Hello>,
+) -> actix_web::Result<web::Json<Presence>> {
+    let id = board_id(id)?;
+
+    let board = existing_board(&id)?;
+
+    let mut board = board.lock().unwrap();
+
+    let participant = board.greet(hello.into_inner());
+
+    Ok(web::Json(Presence {
+        session: participant.session,
+        color: participant.color,
+        participants: board.participants(),
+    }))
+}
+
+#[get("/boards/{id}/num_connections")]
+async fn num_connections(id: web::Path<String>) -> actix_web::Result<String> {
+    let id = board_id(id)?;
+
+    let num_connections = match boards().get(&id) {
+        Some(board) => board.lock().unwrap().participants().len(),
+        None => 0,
+    };
+
+    Ok(num_connections.to_string())
+}
+
+fn cors() -> Cors {
+    if config().allows_any_origin() {
+        return Cors::permissive();
+    }
+
+    config()
+        .cors_origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allow_any_method()
+        .allow_any_header()
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::thread::spawn(|| loop {
-        std::thread::sleep(std::time::Duration::from_secs(10));
-        let now = Instant::now();
-        let mut connections = CONNECTIONS.lock().unwrap();
-        connections.retain(|_, instant| now.duration_since(*instant).as_secs() < 30);
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or(&config.log_level));
+
+    log::info!("Using {:?}", config);
+
+    let config = CONFIG.get_or_init(|| config);
+
+    BOARDS.set(Mutex::new(Boards::load(&config.data_dir)?)).ok();
+
+    std::thread::spawn(move || loop {
+        let timeout = config.presence_timeout();
+
+        std::thread::sleep((timeout / 3).max(std::time::Duration::from_secs(1)));
+        for board in boards().iter() {
+            board.lock().unwrap().forget_idle(timeout);
+        }
     });
 
-    HttpServer::new(|| {
-        let cors = Cors::permissive();
+    HttpServer::new(move || {
         App::new()
-            .wrap(cors)
+            .wrap(cors())
+            .app_data(web::JsonConfig::default().limit(config.max_payload_bytes))
             .service(greet)
+            .service(list_boards)
+            .service(create_board)
+            .service(delete_board)
             .service(get_lines)
-            .service(post_lines)
-            .service(remove_lines)
-            .service(clear_lines)
+            .service(post_operations)
+            .service(board_events)
+            .service(export_svg)
+            .service(export_png)
+            .service(get_presence)
+            .service(post_presence)
             .service(num_connections)
     })
-    .bind(("0.0.0.0", 8432))?
+    .bind((config.bind.as_str(), config.port))?
     .run()
     .await
 }
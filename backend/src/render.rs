@@ -0,0 +1,181 @@
+use ab_glyph::{Font, FontRef, OutlineCurve, PxScale, ScaleFont};
+use shared::{export_size, Line, Lines, ROW_HEIGHT};
+use tiny_skia::{
+    Color, FillRule, FilterQuality, LineCap, LineJoin, Paint, PathBuilder, Pixmap, PixmapPaint,
+    Stroke, Transform,
+};
+
+/// Largest width a PNG export can be requested at.
+pub const MAX_WIDTH: u32 = 4096;
+
+/// Rasterizes `lines`, which are in normalized coordinates, over the background as a PNG `width` pixels wide.
+///
+/// The height follows the aspect ratio of the background and strokes are scaled with the image,
+/// so the result looks like the SVG export at any width. Hidden layers are left out like there.
+pub fn render_png(
+    lines: &Lines,
+    background_png: Option<&[u8]>,
+    width: u32,
+) -> Result<Vec<u8>, String> {
+    let size = export_size(background_png);
+
+    let scale = width as f32 / size.x;
+    let height = ((size.y * scale).round() as u32).max(1);
+
+    let mut pixmap = Pixmap::new(width, height).ok_or("invalid image size")?;
+
+    if let Some(png) = background_png {
+        let background = Pixmap::decode_png(png).map_err(|e| e.to_string())?;
+
+        pixmap.draw_pixmap(
+            0,
+            0,
+            background.as_ref(),
+            &PixmapPaint {
+                quality: FilterQuality::Bicubic,
+                ..Default::default()
+            },
+            Transform::from_scale(
+                width as f32 / background.width() as f32,
+                height as f32 / background.height() as f32,
+            ),
+            None,
+        );
+    }
+
+    // The proportional font of the canvas.
+    let font =
+        FontRef::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT).map_err(|e| e.to_string())?;
+
+    // Same order and filter as the canvas draws them.
+    for (_, line) in lines
+        .drawing_order()
+        .into_iter()
+        .filter(|(_, line)| line.len() >= 2)
+    {
+        let [r, g, b, a] = line.stroke.color.to_srgba_unmultiplied();
+
+        let mut paint = Paint::default();
+        paint.set_color(Color::from_rgba8(r, g, b, a));
+        paint.anti_alias = true;
+
+        if line.text_layout().is_some() {
+            let mut scaled = line.clone();
+
+            for pos in scaled.iter_mut() {
+                pos.x *= width as f32;
+                pos.y *= height as f32;
+            }
+
+            fill_text(&mut pixmap, &font, &scaled, &paint);
+
+            continue;
+        }
+
+        let mut path = PathBuilder::new();
+
+        for points in line.paths() {
+            path.move_to(points[0].x * width as f32, points[0].y * height as f32);
+
+            for pos in &points[1..] {
+                path.line_to(pos.x * width as f32, pos.y * height as f32);
+            }
+        }
+
+        let Some(path) = path.finish() else {
+            continue;
+        };
+
+        let stroke = Stroke {
+            width: line.stroke.width * scale,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..Default::default()
+        };
+
+        pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+    }
+
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+/// Fills the glyphs of `line`, which is text in pixel coordinates of the image.
+fn fill_text(pixmap: &mut Pixmap, font: &FontRef<'_>, line: &Line, paint: &Paint) {
+    let Some(layout) = line.text_layout() else {
+        return;
+    };
+
+    let scaled = font.as_scaled(PxScale::from(layout.font_size));
+    let factor = scaled.scale_factor();
+
+    let mut path = PathBuilder::new();
+
+    for (row, text) in line.text.lines().enumerate() {
+        let baseline = scaled.ascent() + row as f32 * ROW_HEIGHT * layout.font_size;
+        let mut x = 0.0;
+
+        for c in text.chars() {
+            let glyph = font.glyph_id(c);
+
+            // Glyph outlines are in font units with y pointing up.
+            let place = |pos: ab_glyph::Point| {
+                (
+                    x + pos.x * factor.horizontal,
+                    baseline - pos.y * factor.vertical,
+                )
+            };
+
+            let mut last = None;
+
+            for curve in font
+                .outline(glyph)
+                .map(|outline| outline.curves)
+                .unwrap_or_default()
+            {
+                let (start, end) = match curve {
+                    OutlineCurve::Line(start, end) => (start, end),
+                    OutlineCurve::Quad(start, _, end) => (start, end),
+                    OutlineCurve::Cubic(start, _, _, end) => (start, end),
+                };
+
+                if last != Some(start) {
+                    let (x, y) = place(start);
+
+                    path.close();
+                    path.move_to(x, y);
+                }
+
+                last = Some(end);
+
+                match curve {
+                    OutlineCurve::Line(_, end) => {
+                        let (x, y) = place(end);
+                        path.line_to(x, y);
+                    }
+                    OutlineCurve::Quad(_, control, end) => {
+                        let ((x1, y1), (x, y)) = (place(control), place(end));
+                        path.quad_to(x1, y1, x, y);
+                    }
+                    OutlineCurve::Cubic(_, control1, control2, end) => {
+                        let ((x1, y1), (x2, y2), (x, y)) =
+                            (place(control1), place(control2), place(end));
+                        path.cubic_to(x1, y1, x2, y2, x, y);
+                    }
+                }
+            }
+
+            path.close();
+
+            x += scaled.h_advance(glyph);
+        }
+    }
+
+    let Some(path) = path.finish() else {
+        return;
+    };
+
+    let transform = Transform::from_rotate(layout.angle.to_degrees())
+        .post_translate(layout.pos.x, layout.pos.y);
+
+    pixmap.fill_path(&path, paint, FillRule::Winding, transform, None);
+}
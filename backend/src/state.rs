@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use shared::{Delta, LineId, Lines, Operation};
+
+/// The lines of a board together with the revision each of them was last changed at.
+#[derive(Default, Serialize, Deserialize)]
+pub struct BoardState {
+    pub lines: Lines,
+    revision: u64,
+    changed: BTreeMap<LineId, u64>,
+}
+
+impl BoardState {
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn apply(&mut self, operation: Operation) {
+        self.revision += 1;
+
+        let revision = self.revision;
+
+        let ids = match &operation {
+            Operation::AddLine { id, .. } | Operation::AppendPoints { id, .. } => vec![*id],
+            Operation::RemoveLines { ids, .. } | Operation::Transform { ids, .. } => ids.clone(),
+            // Layers are part of every delta, see `Lines::subset`.
+            Operation::Clear { .. } | Operation::SetLayer { .. } => Vec::new(),
+        };
+
+        let clear = matches!(operation, Operation::Clear { .. });
+
+        self.lines.apply(operation);
+
+        for id in ids {
+            if self.lines.contains_state(&id) {
+                self.changed.insert(id, revision);
+            }
+        }
+
+        // A clear forgets the lines it removed, the clear itself is part of every delta.
+        if clear {
+            self.changed.retain(|id, _| self.lines.contains_state(id));
+        }
+    }
+
+    /// Everything that changed after revision `since`.
+    ///
+    /// Falls back to the full board if `since` is not a revision of this board.
+    pub fn delta_since(&self, since: u64) -> Delta {
+        if since == 0 || since > self.revision {
+            return Delta {
+                revision: self.revision,
+                full: true,
+                lines: self.lines.clone(),
+            };
+        }
+
+        let ids = self
+            .changed
+            .iter()
+            .filter(|(_, revision)| **revision > since)
+            .map(|(id, _)| *id);
+
+        Delta {
+            revision: self.revision,
+            full: false,
+            lines: self.lines.subset(ids),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::{Line, Timestamp};
+
+    use super::*;
+
+    fn at(counter: u64) -> Timestamp {
+        Timestamp { counter, client: 1 }
+    }
+
+    /// Adds a line that is created at counter `id` and has it as id.
+    fn add(id: u64) -> Operation {
+        Operation::AddLine {
+            id: at(id).id(),
+            line: Line::default(),
+            timestamp: at(id),
+        }
+    }
+
+    fn ids(delta: &Delta) -> Vec<u64> {
+        delta.lines.keys().map(|id| id.counter).collect()
+    }
+
+    #[test]
+    fn deltas_hold_the_lines_changed_after_a_revision() {
+        let mut state = BoardState::default();
+
+        state.apply(add(1));
+        state.apply(add(2));
+        state.apply(Operation::RemoveLines {
+            ids: vec![at(1).id()],
+            timestamp: at(3),
+        });
+
+        let delta = state.delta_since(2);
+
+        assert_eq!(delta.revision, 3);
+        assert!(!delta.full);
+        assert!(ids(&delta).is_empty());
+        assert!(delta.lines.contains_state(&at(1).id()));
+        assert!(!delta.lines.contains_state(&at(2).id()));
+
+        assert!(state.delta_since(0).full);
+        assert_eq!(ids(&state.delta_since(1)), [2]);
+        assert!(ids(&state.delta_since(3)).is_empty());
+
+        // A revision of another board, like one that was deleted and made again.
+        assert!(state.delta_since(4).full);
+
+        let mut lines = state.delta_since(0).lines;
+
+        state.apply(add(4));
+        state.delta_since(3).apply(&mut lines);
+
+        assert_eq!(lines, state.lines);
+    }
+
+    #[test]
+    fn a_clear_reaches_clients_with_a_partial_delta() {
+        let mut state = BoardState::default();
+
+        state.apply(add(1));
+        state.apply(add(2));
+
+        let mut lines = state.delta_since(0).lines;
+
+        state.apply(Operation::Clear { timestamp: at(3) });
+        state.apply(add(4));
+
+        assert!(!state.lines.contains_state(&at(1).id()));
+        assert_eq!(state.changed.keys().collect::<Vec<_>>(), [&at(4).id()]);
+
+        let delta = state.delta_since(2);
+
+        assert!(!delta.full);
+        assert_eq!(ids(&delta), [4]);
+
+        delta.apply(&mut lines);
+
+        assert_eq!(lines.keys().collect::<Vec<_>>(), [&at(4).id()]);
+    }
+}
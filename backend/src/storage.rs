@@ -0,0 +1,312 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use shared::Operation;
+
+use crate::state::BoardState;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// Number of journal entries after which the journal is folded into a new snapshot.
+const COMPACT_AFTER: usize = 1000;
+
+/// Snapshot plus append-only journal of a board on disk.
+///
+/// Every change is appended to the journal and synced before the request returns.
+/// Snapshots are written to a temporary file and renamed into place, so a crash
+/// leaves either the old or the new snapshot but never a partial one.
+///
+/// A failed append is cut off the journal again, so the next entry starts on a line of its
+/// own. If that fails too the storage takes no more changes.
+pub struct Storage {
+    dir: PathBuf,
+    journal: File,
+    journal_entries: usize,
+    /// A partial entry may be left at the end of the journal.
+    failed: bool,
+}
+
+impl Storage {
+    /// Opens the storage in `dir` and returns it together with the restored board.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<(Self, BoardState)> {
+        let dir = dir.into();
+
+        fs::create_dir_all(&dir)?;
+
+        let mut state = read_snapshot(&dir.join(SNAPSHOT_FILE))?;
+
+        let journal_path = dir.join(JOURNAL_FILE);
+
+        let mut journal_entries = 0;
+        let mut unreadable = false;
+
+        if journal_path.exists() {
+            let journal = fs::read(&journal_path)?;
+
+            let lines: Vec<&[u8]> = journal
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.trim_ascii().is_empty())
+                .collect();
+
+            for (index, line) in lines.iter().enumerate() {
+                match serde_json::from_slice::<Operation>(line) {
+                    Ok(operation) => {
+                        state.apply(operation);
+                        journal_entries += 1;
+                    }
+                    // Only the last write can be torn by a crash.
+                    Err(e) if index + 1 == lines.len() => {
+                        log::warn!("Ignoring torn journal tail in {:?}: {}", journal_path, e);
+                        unreadable = true;
+                    }
+                    // Entries after it were acknowledged, so they are kept.
+                    Err(e) => {
+                        log::error!(
+                            "Skipping unreadable journal entry {} in {:?}: {}",
+                            index + 1,
+                            journal_path,
+                            e
+                        );
+                        unreadable = true;
+                    }
+                }
+            }
+        }
+
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)?;
+
+        let mut storage = Self {
+            dir,
+            journal,
+            journal_entries,
+            failed: false,
+        };
+
+        // Rewritten so the unreadable entries are gone before anything is appended.
+        if unreadable {
+            storage.compact(&state)?;
+        }
+
+        log::info!(
+            "Loaded {} lines at revision {} from {:?} ({} journal entries)",
+            state.lines.len(),
+            state.revision(),
+            storage.dir,
+            storage.journal_entries
+        );
+
+        Ok((storage, state))
+    }
+
+    /// Appends `operations` to the journal before they are applied in memory.
+    pub fn record(&mut self, operations: &[Operation]) -> io::Result<()> {
+        if self.failed {
+            return Err(io::Error::other(format!(
+                "Journal in {:?} could not be repaired after a failed write",
+                self.dir
+            )));
+        }
+
+        let mut entries = Vec::new();
+
+        for operation in operations {
+            serde_json::to_writer(&mut entries, operation)?;
+            entries.push(b'\n');
+        }
+
+        let len = self.journal.metadata()?.len();
+
+        if let Err(e) = self
+            .journal
+            .write_all(&entries)
+            .and_then(|_| self.journal.sync_data())
+        {
+            // A partial entry would run into the next one and make both unreadable.
+            if let Err(truncate_error) = self
+                .journal
+                .set_len(len)
+                .and_then(|_| self.journal.sync_data())
+            {
+                log::error!(
+                    "Cannot cut the failed write off the journal in {:?}: {}",
+                    self.dir,
+                    truncate_error
+                );
+
+                self.failed = true;
+            }
+
+            return Err(e);
+        }
+
+        self.journal_entries += operations.len();
+
+        Ok(())
+    }
+
+    /// Folds the journal into a new snapshot once it has grown large enough.
+    pub fn compact_if_needed(&mut self, state: &BoardState) -> io::Result<()> {
+        if self.journal_entries >= COMPACT_AFTER {
+            self.compact(state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `state` as the new snapshot and truncates the journal.
+    pub fn compact(&mut self, state: &BoardState) -> io::Result<()> {
+        let snapshot_path = self.dir.join(SNAPSHOT_FILE);
+        let tmp_path = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            serde_json::to_writer(&mut tmp, state)?;
+            tmp.sync_all()?;
+        }
+
+        fs::rename(&tmp_path, &snapshot_path)?;
+        sync_dir(&self.dir)?;
+
+        self.journal.set_len(0)?;
+        self.journal.sync_all()?;
+        self.journal_entries = 0;
+        self.failed = false;
+
+        log::debug!("Compacted journal in {:?}", self.dir);
+
+        Ok(())
+    }
+}
+
+fn read_snapshot(path: &Path) -> io::Result<BoardState> {
+    if !path.exists() {
+        return Ok(BoardState::default());
+    }
+
+    let snapshot = fs::read_to_string(path)?;
+
+    Ok(serde_json::from_str(&snapshot)?)
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::{Line, Timestamp};
+
+    use super::*;
+
+    /// An empty directory of its own for every test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("storage-{}-{}", std::process::id(), name));
+
+        fs::remove_dir_all(&dir).ok();
+
+        dir
+    }
+
+    fn add(id: u64) -> Operation {
+        let timestamp = Timestamp {
+            counter: id,
+            client: 1,
+        };
+
+        Operation::AddLine {
+            id: timestamp.id(),
+            line: Line::default(),
+            timestamp,
+        }
+    }
+
+    fn counters(state: &BoardState) -> Vec<u64> {
+        state.lines.keys().map(|id| id.counter).collect()
+    }
+
+    fn append(dir: &Path, bytes: &[u8]) {
+        OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .unwrap()
+            .write_all(bytes)
+            .unwrap();
+    }
+
+    #[test]
+    fn journal_is_replayed_and_compacted_into_the_snapshot() {
+        let dir = temp_dir("replay");
+
+        let (mut storage, mut state) = Storage::open(&dir).unwrap();
+
+        for operations in [vec![add(1), add(2)], vec![add(3)]] {
+            storage.record(&operations).unwrap();
+
+            for operation in operations {
+                state.apply(operation);
+            }
+        }
+
+        drop(storage);
+
+        let (mut storage, replayed) = Storage::open(&dir).unwrap();
+
+        assert_eq!(storage.journal_entries, 3);
+        assert_eq!(replayed.revision(), 3);
+        assert_eq!(replayed.lines, state.lines);
+
+        storage.compact(&replayed).unwrap();
+
+        assert_eq!(fs::metadata(dir.join(JOURNAL_FILE)).unwrap().len(), 0);
+
+        let (storage, compacted) = Storage::open(&dir).unwrap();
+
+        assert_eq!(storage.journal_entries, 0);
+        assert_eq!(compacted.revision(), 3);
+        assert_eq!(compacted.lines, state.lines);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_entries_before_the_tail_do_not_drop_later_ones() {
+        let dir = temp_dir("unreadable");
+
+        let (mut storage, _) = Storage::open(&dir).unwrap();
+        storage.record(&[add(1)]).unwrap();
+        drop(storage);
+
+        // A write that failed halfway, followed by an entry that was acknowledged.
+        append(&dir, b"{\"AddLine\":{\"id\":2,\"li\n");
+        append(
+            &dir,
+            &[serde_json::to_vec(&add(3)).unwrap(), b"\n".to_vec()].concat(),
+        );
+
+        // A write torn by a crash.
+        append(&dir, b"{\"AddLine\":");
+
+        let (_, state) = Storage::open(&dir).unwrap();
+
+        assert_eq!(counters(&state), [1, 3]);
+
+        let (_, state) = Storage::open(&dir).unwrap();
+
+        assert_eq!(counters(&state), [1, 3]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
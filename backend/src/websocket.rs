@@ -0,0 +1,83 @@
+use std::{
+    sync::{Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use actix_ws::{Message, MessageStream, Session};
+use shared::Event;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::boards::Board;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Forwards the board's changes to one websocket client until either side goes away.
+///
+/// A client that falls too far behind is disconnected instead of being sent a partial
+/// history, it is expected to reconnect and fetch the full board again.
+///
+/// Cursors the client sends are passed on to everyone on the board.
+///
+/// Only a weak reference to the board is kept, so once the board is deleted its events end and
+/// the client is disconnected.
+pub async fn run(
+    board: Weak<Mutex<Board>>,
+    connection: String,
+    mut events: broadcast::Receiver<String>,
+    mut session: Session,
+    mut messages: MessageStream,
+) {
+    let mut heartbeat = actix_web::rt::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_heartbeat = Instant::now();
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if session.text(event).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Disconnecting {} after it missed {} changes", connection, skipped);
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = messages.recv() => match message {
+                Some(Ok(Message::Ping(bytes))) => {
+                    last_heartbeat = Instant::now();
+
+                    if session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => {
+                    last_heartbeat = Instant::now();
+                }
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<Event>(&text) {
+                    Ok(Event::Cursor { cursor }) => match board.upgrade() {
+                        Some(board) => board.lock().unwrap().publish_cursor(cursor),
+                        None => break,
+                    },
+                    _ => log::debug!("Ignoring message from {}: {}", connection, text),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = heartbeat.tick() => {
+                if last_heartbeat.elapsed() > CLIENT_TIMEOUT {
+                    log::info!("Websocket of {} timed out", connection);
+                    break;
+                }
+
+                if session.ping(b"").await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    session.close(None).await.ok();
+}
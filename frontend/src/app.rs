@@ -1,34 +1,121 @@
 use core::num;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::time::Duration;
 
 use egui::{
     emath, pos2, vec2, Color32, ColorImage, ComboBox, Context, Frame, Pos2, Rect, Sense, Stroke,
     TextureId, TextureOptions, Ui, Window,
 };
-use egui::{Style, TextureHandle};
+use egui::{Key, KeyboardShortcut, Modifiers, Style, TextureHandle};
 use getrandom::getrandom;
 use log::debug;
-use shared::{Line, Lines};
+use shared::{
+    Clock, Cursor, Delta, Event, Hello, Layer, Line, LineId, Lines, Operation, Participant,
+    Presence, Shape, TextLayout, Timestamp, Transform, DEFAULT_LAYER,
+};
 
 use std::ops::Add;
 
-use crate::requests::{execute, send_get_request, send_post_request};
+use crate::client::{BackendClient, HttpClient};
+use crate::history::History;
+use crate::pending::Pending;
+use crate::requests::TransportError;
+use crate::selection::{self, Handle, Selection};
+use crate::settings::Settings;
+use crate::status::SyncStatus;
+use crate::websocket::{EventSocket, SocketEvent};
 
 const IMAGES: &[(&str, &[u8])] =
     &include!(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/images.in"));
 
 const UPDATE_FREQUENCY: f64 = 1.0;
 
+/// Seconds between sending the new points of the line that is being drawn.
+const STREAM_INTERVAL: f64 = 0.1;
+
+/// Seconds between telling the backend we are still on the board.
+const PRESENCE_INTERVAL: f64 = 5.0;
+
+/// Seconds without a hello after which a participant is shown as idle.
+const IDLE_AFTER: f64 = 15.0;
+
+/// Seconds between sending where our pointer is.
+const CURSOR_INTERVAL: f64 = 0.1;
+
+/// Seconds a remote cursor stays fully visible after it last moved, it fades out after that.
+const CURSOR_IDLE: f64 = 3.0;
+
+/// Seconds a remote cursor takes to fade out.
+const CURSOR_FADE: f64 = 2.0;
+
+/// Seconds to wait before trying to reconnect a closed websocket.
+const RECONNECT_INTERVAL: f64 = 5.0;
+
+/// Pixels the pointer may be off a line or handle to grab it with the selection tool.
+const PICK_RADIUS: f32 = 6.0;
+
+/// How far duplicated lines are moved away from the originals.
+const DUPLICATE_OFFSET: f32 = 10.0;
+
+/// Pixels around the pointer the eraser reaches.
+const ERASER_RADIUS: f32 = 10.0;
+
+/// Font size of new text at the original canvas size.
+const FONT_SIZE: f32 = 24.0;
+
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+const DUPLICATE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::D);
+
 pub struct Channel<T> {
     sender: std::sync::mpsc::Sender<T>,
     receiver: std::sync::mpsc::Receiver<T>,
 }
 
+/// What the primary button does on the canvas.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Draw(Shape),
+    Text,
+    Select,
+}
+
+/// Text being typed with the text tool.
+struct EditedText {
+    id: LineId,
+    /// In canvas coordinates, its text is edited in place.
+    line: Line,
+    /// Whether the editor got the keyboard focus, losing it after that finishes the text.
+    focused: bool,
+}
+
+/// Where another participant is pointing.
+struct RemoteCursor {
+    /// In canvas coordinates, like the lines.
+    pos: Pos2,
+    last_moved: web_time::Instant,
+}
+
 pub struct App {
-    location: eframe::Location,
+    settings: Settings,
+    client: Box<dyn BackendClient>,
     lines: Lines,
-    lines_already_synced: HashSet<u64>,
+    /// The line being drawn, kept apart from `lines` until it is finished.
+    current_line: Line,
+    /// Timestamp of the current line, taken when it is first sent, its id is made from it.
+    current_timestamp: Option<Timestamp>,
+    clock: Clock,
+    history: History,
+    tool: Tool,
+    selection: Selection,
+    /// Whether the eraser cuts out only the touched part of lines instead of removing them.
+    precise_eraser: bool,
+    /// Layer new lines are drawn on.
+    active_layer: u64,
+    /// Layer whose name is being edited in the layers panel, with the new name.
+    renamed_layer: Option<(u64, String)>,
+    show_layers: bool,
     stroke: Stroke,
     scroll_speed: f32,
     current_background_id: TextureId,
@@ -36,28 +123,94 @@ pub struct App {
     zoom: f32,
     original_canvas_rect: Option<Rect>,
     texture_handles: HashMap<TextureId, TextureHandle>,
-    new_lines_channel: Channel<Lines>,
-    num_connections_channel: Channel<u64>,
+    /// Fetched deltas with the revision they were asked for after.
+    new_lines_channel: Channel<(u64, Delta)>,
+    presence_channel: Channel<Presence>,
+    /// Round trips of finished fetches and hellos, or why they failed.
+    outcome_channel: Channel<Result<Duration, TransportError>>,
+    /// Results of posts by their number in `pending`.
+    sent_channel: Channel<(u64, Result<(), TransportError>)>,
+    /// Results of creating the board.
+    created_channel: Channel<Result<(), TransportError>>,
+    /// Presence and the websocket wait for the board to be created, they do not create it.
+    board_created: bool,
+    last_create_attempt: Option<web_time::Instant>,
+    /// Last error talking to the backend, shown until a request succeeds or it is retried.
+    sync_error: Option<TransportError>,
+    /// Number of changes the backend refused and that were dropped, with why, until dismissed.
+    rejected: Option<(usize, TransportError)>,
+    pending: Pending,
+    status: SyncStatus,
+    /// Name shown to the others on the board.
+    name: String,
+    /// Assigned by the backend on the first hello.
+    session: Option<u64>,
+    participants: Vec<Participant>,
+    /// Color the backend assigned, which is the stroke color until the user picks another one.
+    assigned_color: Option<Color32>,
+    custom_color: bool,
+    last_hello: Option<web_time::Instant>,
+    show_presence: bool,
+    cursors: HashMap<u64, RemoteCursor>,
+    /// Last position of our pointer that was sent, in canvas coordinates.
+    sent_cursor: Option<Pos2>,
+    last_cursor_sent: web_time::Instant,
     last_update: web_time::Instant,
-    last_id: u64,
+    revision: u64,
+    streamed_len: usize,
+    /// Where the drag that spans the current shape started.
+    shape_start: Option<Pos2>,
+    font_size: f32,
+    editing_text: Option<EditedText>,
+    last_stream: web_time::Instant,
+    socket: Option<EventSocket>,
+    socket_open: bool,
+    last_connect_attempt: Option<web_time::Instant>,
 }
 
-const RANDOM_COLORS: &[Color32; 8] = &[
-    Color32::RED,
-    Color32::GREEN,
-    Color32::BLUE,
-    Color32::YELLOW,
-    Color32::ORANGE,
-    Color32::LIGHT_BLUE,
-    Color32::KHAKI,
-    Color32::GOLD,
-];
-
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        log::info!("{:?}", cc.integration_info.web_info.location);
+    pub fn new(cc: &eframe::CreationContext<'_>, settings: Settings) -> Self {
+        let client = Box::new(HttpClient::new(settings.clone()));
+
+        let mut app = Self::with_client(&cc.egui_ctx, settings, client);
+
+        if let Some(storage) = cc.storage {
+            app.load(storage);
+        }
+
+        app
+    }
+
+    /// Restores what [`eframe::App::save`] stored for the board.
+    ///
+    /// The clock continues from the last session, so lines drawn before the board is fetched
+    /// are not created below lines this client drew earlier.
+    fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(timestamp) = eframe::get_value::<Timestamp>(storage, &self.clock_key()) {
+            self.clock.observe(timestamp);
+        }
+
+        // Changes that did not reach the backend before the app was closed.
+        if let Some(operations) = eframe::get_value::<Vec<Operation>>(storage, &self.pending_key())
+            .filter(|operations| !operations.is_empty())
+        {
+            log::info!("Restored {} pending operations", operations.len());
+
+            for operation in &operations {
+                self.clock.observe(operation.timestamp());
+            }
+
+            self.pending = Pending::restore(operations);
+        }
+    }
 
-        let location = cc.integration_info.web_info.location.clone();
+    /// Draws on the board of `settings` through `client`, like a [`crate::FakeClient`].
+    pub fn with_client(
+        ctx: &egui::Context,
+        settings: Settings,
+        client: Box<dyn BackendClient>,
+    ) -> Self {
+        log::info!("Using {:?}", settings);
 
         let texture_handles: HashMap<TextureId, TextureHandle> = IMAGES
             .iter()
@@ -69,7 +222,7 @@ impl App {
 
                 let name = file_name.to_string().replace(".png", "");
 
-                let texture = cc.egui_ctx.load_texture(
+                let texture = ctx.load_texture(
                     name,
                     load_image_from_memory(data).unwrap(),
                     TextureOptions::default(),
@@ -79,51 +232,67 @@ impl App {
             })
             .collect();
 
-        let lines_channel = std::sync::mpsc::channel::<Lines>();
+        let lines_channel = std::sync::mpsc::channel::<(u64, Delta)>();
 
         let lines_channel = Channel {
             sender: lines_channel.0,
             receiver: lines_channel.1,
         };
 
-        let num_connections_chanenl = std::sync::mpsc::channel::<u64>();
+        let presence_channel = std::sync::mpsc::channel::<Presence>();
 
-        let num_connections_channel = Channel {
-            sender: num_connections_chanenl.0,
-            receiver: num_connections_chanenl.1,
+        let presence_channel = Channel {
+            sender: presence_channel.0,
+            receiver: presence_channel.1,
         };
 
-        let sender = num_connections_channel.sender.clone();
+        let outcome_channel = std::sync::mpsc::channel();
 
-        let url = location.url.clone();
+        let outcome_channel = Channel {
+            sender: outcome_channel.0,
+            receiver: outcome_channel.1,
+        };
 
-        execute(async move {
-            let result = send_get_request(&format!("{}/backend/num_connections", url)).await;
+        let sent_channel = std::sync::mpsc::channel();
 
-            match result {
-                Ok(num_connections) => {
-                    sender
-                        .send(num_connections.parse::<u64>().unwrap_or_default())
-                        .unwrap();
-                }
-                Err(e) => {
-                    log::error!("Error: {:?}", e);
-                }
-            }
-        });
+        let sent_channel = Channel {
+            sender: sent_channel.0,
+            receiver: sent_channel.1,
+        };
 
-        let random_number = get_random_u64();
+        let created_channel = std::sync::mpsc::channel();
 
-        let random_color = RANDOM_COLORS[random_number as usize % RANDOM_COLORS.len()];
+        let created_channel = Channel {
+            sender: created_channel.0,
+            receiver: created_channel.1,
+        };
+
+        let name = settings
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Guest {}", get_random_u64() % 1000));
+
+        // Replaced by the color the backend assigns, unless the user picked one before that.
+        let stroke = Stroke {
+            width: 3.0,
+            color: shared::participant_color(0),
+        };
 
         Self {
-            location,
+            settings,
+            client,
             lines: Default::default(),
-            lines_already_synced: Default::default(),
-            stroke: Stroke {
-                width: 3.0,
-                color: random_color,
-            },
+            current_line: Line::new(stroke),
+            current_timestamp: None,
+            clock: Clock::new(get_random_u64()),
+            history: History::default(),
+            tool: Tool::Draw(Shape::Freehand),
+            selection: Selection::default(),
+            precise_eraser: true,
+            active_layer: DEFAULT_LAYER,
+            renamed_layer: None,
+            show_layers: false,
+            stroke,
             scroll_speed: 10.0,
             current_background_id: texture_handles.keys().next().unwrap().to_owned(),
             texture_handles,
@@ -131,341 +300,1673 @@ impl App {
             zoom: 0.0,
             original_canvas_rect: None,
             new_lines_channel: lines_channel,
-            num_connections_channel,
+            presence_channel,
+            outcome_channel,
+            sent_channel,
+            created_channel,
+            board_created: false,
+            last_create_attempt: None,
+            sync_error: None,
+            rejected: None,
+            pending: Pending::default(),
+            status: SyncStatus::default(),
+            name,
+            session: None,
+            participants: Vec::new(),
+            assigned_color: None,
+            custom_color: false,
+            last_hello: None,
+            show_presence: true,
+            cursors: HashMap::new(),
+            sent_cursor: None,
+            last_cursor_sent: web_time::Instant::now(),
             last_update: web_time::Instant::now(),
-            last_id: 0,
+            revision: 0,
+            streamed_len: 0,
+            shape_start: None,
+            font_size: FONT_SIZE,
+            editing_text: None,
+            last_stream: web_time::Instant::now(),
+            socket: None,
+            socket_open: false,
+            last_connect_attempt: None,
         }
     }
-}
 
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if self.last_update.elapsed().as_secs_f64() > UPDATE_FREQUENCY {
-            log::info!("Getting lines from backend");
-            let sender = self.new_lines_channel.sender.clone();
+    fn request_lines(&self, original_canvas_rect: Rect) {
+        log::info!("Getting lines from backend");
 
-            if let Some(original_canvas_rect) = self.original_canvas_rect.as_ref() {
-                let original_canvas_rect = original_canvas_rect.clone();
+        let sender = self.new_lines_channel.sender.clone();
+        let outcomes = self.outcome_channel.sender.clone();
+        let started = web_time::Instant::now();
+        let since = self.revision;
 
-                let url = self.location.origin.clone();
+        self.client.fetch_lines(
+            since,
+            Box::new(move |result| match result {
+                Ok(mut delta) => {
+                    outcomes.send(Ok(started.elapsed())).ok();
 
-                execute(async move {
-                    let lines: Result<String, eframe::wasm_bindgen::JsValue> =
-                        send_get_request(&format!("{}/backend/lines", url)).await;
+                    delta.lines.to_canvas(&original_canvas_rect);
 
-                    match lines {
-                        Ok(lines) => {
-                            let mut lines: Lines = lines.into();
+                    sender.send((since, delta)).ok();
+                }
+                Err(error) => {
+                    log::error!("Error: {}", error);
 
-                            lines.to_canvas(&original_canvas_rect);
+                    outcomes.send(Err(error)).ok();
+                }
+            }),
+        );
+    }
 
-                            sender.send(lines).unwrap();
-                        }
-                        Err(e) => {
-                            log::error!("Error: {:?}", e);
-                        }
-                    }
-                });
+    /// Creates the board when the app opens it, again after a while if that failed.
+    ///
+    /// Returns whether the board exists.
+    fn update_board(&mut self) -> bool {
+        if let Ok(result) = self.created_channel.receiver.try_recv() {
+            match result {
+                Ok(()) => self.board_created = true,
+                Err(error) => {
+                    log::error!("Error: {}", error);
+
+                    self.outcome_channel.sender.send(Err(error)).ok();
+                }
             }
+        }
 
-            self.last_update = web_time::Instant::now();
+        let create_due = self.last_create_attempt.map_or(true, |attempt| {
+            attempt.elapsed().as_secs_f64() > RECONNECT_INTERVAL
+        });
+
+        if !self.board_created && create_due {
+            self.last_create_attempt = Some(web_time::Instant::now());
+
+            let sender = self.created_channel.sender.clone();
+
+            self.client.create_board(Box::new(move |result| {
+                sender.send(result).ok();
+            }));
         }
 
-        if let Ok(new_lines) = self.new_lines_channel.receiver.try_recv() {
-            log::info!("Updating lines with new lines");
+        self.board_created
+    }
+
+    /// Tells the backend we are on the board, the answer says who else is.
+    fn say_hello(&mut self) {
+        self.last_hello = Some(web_time::Instant::now());
+
+        let hello = Hello {
+            session: self.session,
+            name: self.name.clone(),
+        };
+
+        let sender = self.presence_channel.sender.clone();
+        let outcomes = self.outcome_channel.sender.clone();
+        let started = web_time::Instant::now();
+
+        self.client.say_hello(
+            hello,
+            Box::new(move |result| match result {
+                Ok(presence) => {
+                    outcomes.send(Ok(started.elapsed())).ok();
+
+                    sender.send(presence).ok();
+                }
+                Err(error) => {
+                    log::error!("Error: {}", error);
 
-            let last_line = self.lines.get(&self.last_id).unwrap().clone();
+                    outcomes.send(Err(error)).ok();
+                }
+            }),
+        );
+    }
+
+    /// Says hello when it is due and takes in the answers.
+    fn update_presence(&mut self) {
+        let hello_due = self.last_hello.map_or(true, |last_hello| {
+            last_hello.elapsed().as_secs_f64() > PRESENCE_INTERVAL
+        });
 
-            self.lines = new_lines;
+        if hello_due {
+            self.say_hello();
+        }
+
+        if let Ok(presence) = self.presence_channel.receiver.try_recv() {
+            self.session = Some(presence.session);
+            self.participants = presence.participants;
+            self.assigned_color = Some(presence.color);
 
-            if !self.lines.contains_key(&self.last_id) {
-                self.lines.insert(self.last_id, last_line);
+            if !self.custom_color {
+                self.stroke.color = presence.color;
             }
         }
+    }
+
+    fn presence_panel(&mut self, ui: &mut Ui) {
+        ui.heading("On this board");
 
-        if let Ok(num_connections) = self.num_connections_channel.receiver.try_recv() {
-            log::debug!("Number of connections: {}", num_connections);
+        ui.horizontal(|ui| {
+            ui.label("Your name");
 
-            // TODO: not working
-            // self.stroke.color = match num_connections {
-            //     0 => Color32::RED,
-            //     1 => Color32::GREEN,
-            //     2 => Color32::YELLOW,
-            //     3 => Color32::ORANGE,
-            //     4 => Color32::LIGHT_BLUE,
-            //     5 => Color32::KHAKI,
-            //     6 => Color32::GOLD,
-            //     _ => Color32::BLUE,
-            // };
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.name)
+                    .char_limit(shared::MAX_NAME_LEN)
+                    .desired_width(f32::INFINITY),
+            );
+
+            if response.lost_focus() {
+                // Sent right away so the others see the new name without waiting.
+                self.last_hello = None;
+            }
+        });
+
+        ui.separator();
+
+        let now = unix_millis();
+
+        for participant in &self.participants {
+            ui.horizontal(|ui| {
+                let (rect, _) = ui.allocate_exact_size(vec2(12.0, 12.0), Sense::hover());
+                ui.painter()
+                    .circle_filled(rect.center(), 6.0, participant.color);
+
+                if Some(participant.session) == self.session {
+                    ui.strong(format!("{} (you)", participant.name));
+                } else {
+                    ui.label(&participant.name);
+                }
+
+                let idle = now.saturating_sub(participant.last_seen) as f64 / 1000.0;
+
+                if idle > IDLE_AFTER {
+                    ui.weak(format!("idle {}s", idle as u64));
+                }
+            });
         }
+    }
 
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            egui::menu::bar(ui, |ui| {
-                egui::widgets::global_theme_preference_buttons(ui);
+    /// Lists the layers from the top to the bottom, to pick the one to draw on and to change them.
+    fn layers_panel(&mut self, ui: &mut Ui) {
+        ui.heading("Layers");
 
-                ComboBox::from_id_salt("Images").show_ui(ui, |ui| {
-                    for (id, handle) in self.texture_handles.iter() {
-                        let name = handle.name().split('.').next().unwrap().to_string();
+        if ui.button("Add layer").clicked() {
+            self.add_layer();
+        }
 
-                        if ui
-                            .selectable_value(&mut self.current_background_id, *id, &name)
-                            .clicked()
-                        {
-                            log::info!("Selected image: {}", name);
-                        }
-                    }
-                });
+        ui.separator();
 
-                ui.add(&mut self.stroke);
+        let layers = self.lines.layers();
 
-                ui.add(egui::Slider::new(&mut self.scroll_speed, 1.0..=20.0).text("Scroll speed"));
+        for (index, (id, layer)) in layers.iter().enumerate().rev() {
+            ui.horizontal(|ui| {
+                let mut changed = layer.clone();
 
-                ui.button("Clear")
-                    .on_hover_text("Clear the canvas")
+                ui.toggle_value(&mut changed.hidden, "👁")
+                    .on_hover_text("Hide the layer");
+                ui.toggle_value(&mut changed.locked, "🔒")
+                    .on_hover_text("Lock the layer against changes");
+
+                if ui
+                    .add_enabled(index + 1 < layers.len(), egui::Button::new("⏶"))
+                    .on_hover_text("Move up")
                     .clicked()
-                    .then(|| {
-                        log::info!("Sending clear request");
+                {
+                    self.swap_layers(&layers[index], &layers[index + 1]);
+                }
 
-                        self.lines.0.clear();
-                        self.lines_already_synced.clear();
+                if ui
+                    .add_enabled(index > 0, egui::Button::new("⏷"))
+                    .on_hover_text("Move down")
+                    .clicked()
+                {
+                    self.swap_layers(&layers[index - 1], &layers[index]);
+                }
 
-                        let url = self.location.origin.clone();
+                match &mut self.renamed_layer {
+                    Some((renamed, name)) if renamed == id => {
+                        let response = ui.text_edit_singleline(name);
 
-                        execute(async move {
-                            match send_post_request(&format!("{}/backend/clear", url), "").await {
-                                Ok(_) => {
-                                    log::debug!("Successfully cleared lines");
-                                }
-                                Err(e) => {
-                                    log::error!("Error: {:?}", e);
-                                }
+                        if response.lost_focus() {
+                            let name = name.trim().to_string();
+
+                            self.renamed_layer = None;
+
+                            if !name.is_empty() {
+                                changed.name = name;
                             }
-                        });
-                    })
+                        } else if !response.has_focus() {
+                            // Focused when it first shows, so typing starts right away.
+                            response.request_focus();
+                        }
+                    }
+                    _ => {
+                        let response = ui
+                            .selectable_label(self.active_layer == *id, &layer.name)
+                            .on_hover_text("Draw on this layer, double-click to rename it");
+
+                        if response.clicked() {
+                            self.active_layer = *id;
+                        }
+
+                        if response.double_clicked() {
+                            self.renamed_layer = Some((*id, layer.name.clone()));
+                        }
+                    }
+                }
+
+                if changed != *layer {
+                    self.set_layer(*id, changed);
+                }
             });
-        });
+        }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            Frame::canvas(ui.style()).show(ui, |ui| {
-                let (mut response, painter) =
-                    ui.allocate_painter(ui.available_size_before_wrap(), Sense::drag());
+    /// Adds a layer on top of all others and draws on it.
+    ///
+    /// Layers cannot be removed, so adding one is not undone.
+    fn add_layer(&mut self) {
+        let layers = self.lines.layers();
 
-                let original_background_size = self
-                    .texture_handles
-                    .get(&self.current_background_id)
-                    .unwrap()
-                    .size();
+        let order = layers.last().map_or(0, |(_, layer)| layer.order + 1);
 
-                let background_size = original_background_size
-                    .iter()
-                    .map(|x| (*x as f32))
-                    .collect::<Vec<f32>>();
+        let id = get_random_u64();
 
-                let offset_x = self.background_offset.x;
-                let offset_y = self.background_offset.y;
+        let timestamp = self.clock.tick();
 
-                let shrink_x = background_size[0] * self.zoom;
-                let shrink_y = background_size[1] * self.zoom;
+        self.commit(Operation::SetLayer {
+            id,
+            layer: Layer::new(format!("Layer {}", layers.len() + 1), order),
+            timestamp,
+        });
 
-                let shrink = emath::vec2(shrink_x, shrink_y);
+        self.active_layer = id;
+    }
 
-                let background_rect = Rect::from_min_size(
-                    Pos2 {
-                        x: offset_x,
-                        y: offset_y,
-                    },
-                    emath::vec2(background_size[0], background_size[1]),
-                )
-                .shrink2(shrink);
+    fn set_layer(&mut self, id: u64, layer: Layer) {
+        let timestamp = self.clock.tick();
 
-                let canvas_size = ui.available_size_before_wrap();
+        self.perform(Operation::SetLayer {
+            id,
+            layer,
+            timestamp,
+        });
+    }
 
-                if self.original_canvas_rect.is_none() {
-                    self.original_canvas_rect = Some(response.rect);
+    /// Moves the layer `upper` below `lower`, which is right below it.
+    fn swap_layers(&mut self, lower: &(u64, Layer), upper: &(u64, Layer)) {
+        let (lower_id, lower) = lower.clone();
+        let (upper_id, upper) = upper.clone();
 
-                    debug!("Original canvas rect: {:?}", self.original_canvas_rect);
-                    debug!("Background rect: {:?}", background_rect);
-                    debug!("Canvas size: {:?}", canvas_size);
-                    debug!("Background size: {:?}", background_size);
-                }
+        // Layers added at the same time by different people can have the same order.
+        let order = match lower.order == upper.order {
+            true => upper.order + 1,
+            false => upper.order,
+        };
 
-                painter.image(
-                    self.current_background_id,
-                    background_rect,
-                    Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
-                    Color32::WHITE,
-                );
+        let operations = vec![
+            Operation::SetLayer {
+                id: lower_id,
+                layer: Layer { order, ..lower },
+                timestamp: self.clock.tick(),
+            },
+            Operation::SetLayer {
+                id: upper_id,
+                layer: Layer {
+                    order: lower.order,
+                    ..upper
+                },
+                timestamp: self.clock.tick(),
+            },
+        ];
 
-                let from_screen = emath::RectTransform::from_to(
-                    background_rect,
-                    self.original_canvas_rect.unwrap(),
-                );
+        self.perform_all(operations);
+    }
 
-                let to_screen = from_screen.inverse();
+    /// Whether lines can be added to the active layer, it is neither hidden nor locked.
+    fn can_draw(&self) -> bool {
+        self.lines
+            .layer(self.active_layer)
+            .map_or(true, Layer::is_editable)
+    }
 
-                let which_mouse_button_down = response.ctx.input(|i| {
-                    if i.pointer.primary_down() {
-                        MouseDown::Primary
-                    } else if i.pointer.secondary_down() {
-                        MouseDown::Secondary
-                    } else if i.pointer.middle_down() {
-                        MouseDown::Middle
-                    } else {
-                        MouseDown::None
-                    }
-                });
+    /// Removes every line that can be edited, lines on hidden and locked layers are left alone.
+    fn clear(&mut self) {
+        let timestamp = self.clock.tick();
 
-                let scroll_delta = response.ctx.input(|i| i.raw_scroll_delta);
+        if self
+            .lines
+            .layers()
+            .iter()
+            .all(|(_, layer)| layer.is_editable())
+        {
+            log::info!("Sending clear request");
 
-                let scroll_delta_y = scroll_delta.y * self.scroll_speed * 1e-4;
+            self.perform(Operation::Clear { timestamp });
 
-                if scroll_delta_y != 0.0 {
-                    self.zoom = (self.zoom - scroll_delta_y).clamp(-2.0, 2.0);
-                }
+            return;
+        }
 
-                if self.lines.0.is_empty() {
-                    let first_id = get_random_u64();
+        let ids: Vec<LineId> = self
+            .lines
+            .keys()
+            .filter(|id| self.lines.is_editable(id))
+            .copied()
+            .collect();
 
-                    self.lines.0.insert(first_id, Line::new(self.stroke));
+        if ids.is_empty() {
+            return;
+        }
 
-                    self.last_id = first_id;
-                }
+        log::info!("Removing lines: {:?}", ids);
 
-                let current_line = self.lines.0.get_mut(&self.last_id).unwrap();
+        self.perform(Operation::RemoveLines { ids, timestamp });
+    }
 
-                match response.interact_pointer_pos() {
-                    Some(pointer_pos) => {
-                        let canvas_pos = from_screen * pointer_pos;
+    /// Moves the selected lines to the active layer.
+    ///
+    /// The lines keep their ids, so they are drawn among the lines already on the layer in the
+    /// order they were created in, not on top of them.
+    fn move_selection_to_active_layer(&mut self) {
+        let operations: Vec<Operation> = self
+            .selection
+            .ids
+            .iter()
+            .filter_map(|id| Some((*id, self.lines.get(id)?.clone())))
+            .filter(|(_, line)| line.layer != self.active_layer)
+            .map(|(id, line)| Operation::AddLine {
+                id,
+                line: Line {
+                    layer: self.active_layer,
+                    ..line
+                },
+                timestamp: self.clock.tick(),
+            })
+            .collect();
 
-                        match which_mouse_button_down {
-                            MouseDown::Primary => {
-                                if current_line.last() != Some(&canvas_pos) {
-                                    current_line.push(canvas_pos);
-                                    response.mark_changed();
-                                }
-                            }
-                            MouseDown::Secondary => {
-                                let mut lines_to_remove: Vec<u64> = Vec::new();
+        if operations.is_empty() {
+            return;
+        }
+
+        self.perform_all(operations);
+    }
 
-                                const TOLERANCE: f32 = 10.0;
+    /// Keeps the websocket connected and applies the changes it receives.
+    ///
+    /// While the socket is not open the board is polled instead.
+    fn update_socket(&mut self, ctx: &egui::Context, original_canvas_rect: Rect) {
+        let should_connect = self.last_connect_attempt.map_or(true, |attempt| {
+            attempt.elapsed().as_secs_f64() > RECONNECT_INTERVAL
+        });
 
-                                for (line_id, line) in self.lines.iter() {
-                                    for point in line.iter() {
-                                        let distance = (*point - canvas_pos).length();
+        if self.socket.is_none() && should_connect {
+            self.last_connect_attempt = Some(web_time::Instant::now());
 
-                                        if distance < TOLERANCE {
-                                            lines_to_remove.push(*line_id);
-                                            break;
-                                        }
-                                    }
-                                }
+            match EventSocket::connect(&self.settings.board_events_url(), ctx) {
+                Ok(socket) => self.socket = Some(socket),
+                Err(e) => log::error!("Error: {:?}", e),
+            }
+        }
 
-                                if !lines_to_remove.is_empty() {
-                                    log::info!("Removing lines: {:?}", lines_to_remove);
-
-                                    for line_id in lines_to_remove.iter() {
-                                        self.lines.remove(line_id);
-                                        self.lines_already_synced.remove(line_id);
-                                    }
-
-                                    let url = self.location.origin.clone();
-
-                                    execute(async move {
-                                        match send_post_request(
-                                            &format!("{}/backend/remove_lines", url),
-                                            &serde_json::to_string(&lines_to_remove).unwrap(),
-                                        )
-                                        .await
-                                        {
-                                            Ok(_) => {
-                                                log::debug!("Successfully removed lines");
-                                            }
-                                            Err(e) => {
-                                                log::error!("Error: {:?}", e);
-                                            }
-                                        }
-                                    });
+        loop {
+            let Some(event) = self.socket.as_ref().and_then(|socket| socket.try_recv()) else {
+                break;
+            };
 
-                                    response.mark_changed();
-                                }
-                            }
-                            MouseDown::Middle => {
-                                let drag_delta = response.drag_delta();
+            match event {
+                SocketEvent::Opened => {
+                    log::info!("Websocket connected");
 
-                                self.background_offset = self.background_offset.add(drag_delta);
-                            }
-                            MouseDown::None => (),
-                        }
-                    }
-                    None => {
-                        if !current_line.is_empty() {
-                            let mut buffer = [0u8; 8];
-                            getrandom(&mut buffer).unwrap();
-                            let id = u64::from_ne_bytes(buffer);
-
-                            let lines = self
-                                .lines
-                                .iter()
-                                .filter_map(|(id, line)| {
-                                    if !self.lines_already_synced.contains(id) {
-                                        self.lines_already_synced.insert(*id);
-
-                                        let mut line = line.clone();
-                                        line.from_canvas(&self.original_canvas_rect.unwrap());
-
-                                        Some((*id, line))
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect::<Lines>();
-
-                            log::info!("Sending lines to backend");
-
-                            let url = self.location.origin.clone();
-
-                            execute(async move {
-                                match send_post_request(
-                                    &format!("{}/backend/lines", url),
-                                    &lines.to_string(),
-                                )
-                                .await
-                                {
-                                    Ok(_) => {
-                                        log::debug!("Successfully sent lines to backend");
-                                    }
-                                    Err(e) => {
-                                        log::error!("Error: {:?}", e);
-                                    }
-                                }
-                            });
+                    self.socket_open = true;
 
-                            self.lines.insert(id, Line::new(self.stroke));
-                            self.last_id = id;
+                    // The websocket only carries changes from now on, fetch the ones made since
+                    // the last revision while it was disconnected.
+                    self.request_lines(original_canvas_rect);
+                }
+                SocketEvent::Message(message) => match serde_json::from_str::<Event>(&message) {
+                    Ok(Event::Operations(envelope)) if envelope.is_supported() => {
+                        self.status.synced();
 
-                            response.mark_changed();
+                        for operation in envelope.operations {
+                            self.apply_remote_operation(operation, &original_canvas_rect);
                         }
                     }
+                    Ok(Event::Operations(envelope)) => {
+                        log::error!("Unsupported protocol version {}", envelope.version)
+                    }
+                    Ok(Event::Cursor { cursor }) => {
+                        self.apply_remote_cursor(cursor, &original_canvas_rect)
+                    }
+                    Err(e) => log::error!("Invalid event from websocket: {:?}", e),
+                },
+                SocketEvent::Closed => {
+                    log::info!("Websocket closed, falling back to polling");
+
+                    self.socket = None;
+                    self.socket_open = false;
+                    self.cursors.clear();
                 }
+            }
+        }
+    }
 
-                let shapes =
-                    self.lines
-                        .iter()
-                        .filter(|(id, line)| line.len() >= 2)
-                        .map(|(id, line)| {
-                            let points: Vec<Pos2> = line.iter().map(|p| to_screen * *p).collect();
-                            egui::Shape::line(points, line.stroke)
-                        });
+    /// Merges a delta fetched with the revision `since`.
+    ///
+    /// Deltas are merged even if they are older than what the app has, like a full board fetched
+    /// before changes that came through the websocket, merging them changes nothing that is newer.
+    fn merge_delta(&mut self, since: u64, delta: Delta) {
+        // The board did not know the revision, it was deleted and created again.
+        if delta.full && since > 0 {
+            log::info!("Board was created again, dropping its old lines");
+
+            self.lines = Lines::default();
+            self.revision = 0;
+        }
 
-                painter.extend(shapes);
+        log::info!(
+            "Updating lines to revision {} ({} changed)",
+            delta.revision,
+            delta.lines.len()
+        );
 
-                response
-            });
-        });
+        // Responses of overlapping requests can arrive out of order.
+        self.revision = self.revision.max(delta.revision);
 
-        ctx.request_repaint_after(Duration::from_secs(1));
+        self.clock.observe(delta.lines.latest_timestamp());
+
+        delta.apply(&mut self.lines);
     }
-}
+
+    /// Applies an operation from the websocket, our own ones come back too and change nothing.
+    fn apply_remote_operation(&mut self, mut operation: Operation, original_canvas_rect: &Rect) {
+        self.clock.observe(operation.timestamp());
+
+        operation.to_canvas(original_canvas_rect);
+
+        self.lines.apply(operation);
+    }
+
+    fn apply_remote_cursor(&mut self, mut cursor: Cursor, original_canvas_rect: &Rect) {
+        if Some(cursor.session) == self.session {
+            return;
+        }
+
+        cursor.to_canvas(original_canvas_rect);
+
+        match cursor.pos {
+            Some(pos) => {
+                self.cursors.insert(
+                    cursor.session,
+                    RemoteCursor {
+                        pos,
+                        last_moved: web_time::Instant::now(),
+                    },
+                );
+            }
+            None => {
+                self.cursors.remove(&cursor.session);
+            }
+        }
+    }
+
+    /// Sends where our pointer is in canvas coordinates, at most every [`CURSOR_INTERVAL`].
+    fn publish_cursor(&mut self, pos: Option<Pos2>) {
+        if pos == self.sent_cursor
+            || self.last_cursor_sent.elapsed().as_secs_f64() < CURSOR_INTERVAL
+        {
+            return;
+        }
+
+        let (Some(session), Some(socket), Some(original_canvas_rect)) =
+            (self.session, &self.socket, self.original_canvas_rect)
+        else {
+            return;
+        };
+
+        if !self.socket_open {
+            return;
+        }
+
+        self.sent_cursor = pos;
+        self.last_cursor_sent = web_time::Instant::now();
+
+        let mut cursor = Cursor { session, pos };
+
+        cursor.from_canvas(&original_canvas_rect);
+
+        match serde_json::to_string(&Event::Cursor { cursor }) {
+            Ok(text) => socket.send(&text),
+            Err(e) => log::error!("Error: {}", e),
+        }
+    }
+
+    /// Draws the cursors of the others with their name, fading out the ones that stopped moving.
+    fn paint_cursors(&mut self, painter: &egui::Painter, to_screen: emath::RectTransform) {
+        self.cursors.retain(|_, cursor| {
+            cursor.last_moved.elapsed().as_secs_f64() < CURSOR_IDLE + CURSOR_FADE
+        });
+
+        for (session, cursor) in &self.cursors {
+            let Some(participant) = self
+                .participants
+                .iter()
+                .find(|participant| participant.session == *session)
+            else {
+                continue;
+            };
+
+            let idle = cursor.last_moved.elapsed().as_secs_f64();
+            let opacity = (1.0 - (idle - CURSOR_IDLE) / CURSOR_FADE).clamp(0.0, 1.0) as f32;
+
+            let color = participant.color.gamma_multiply(opacity);
+            let pos = to_screen * cursor.pos;
+
+            painter.circle(
+                pos,
+                4.0,
+                color,
+                Stroke::new(1.0, Color32::WHITE.gamma_multiply(opacity)),
+            );
+
+            let galley = painter.layout_no_wrap(
+                participant.name.clone(),
+                egui::FontId::proportional(12.0),
+                Color32::WHITE.gamma_multiply(opacity),
+            );
+
+            let label = Rect::from_min_size(pos + vec2(8.0, 8.0), galley.size()).expand(2.0);
+
+            painter.rect_filled(label, 3.0, color);
+            painter.galley(label.min + vec2(2.0, 2.0), galley, Color32::WHITE);
+        }
+
+        if !self.cursors.is_empty() {
+            painter.ctx().request_repaint();
+        }
+    }
+
+    fn current_timestamp(&mut self) -> Timestamp {
+        *self
+            .current_timestamp
+            .get_or_insert_with(|| self.clock.tick())
+    }
+
+    /// Sends the points of the line being drawn that collaborators have not seen yet.
+    ///
+    /// The first chunk adds the line, later chunks are appended to it.
+    fn stream_current_line(&mut self) {
+        if self.last_stream.elapsed().as_secs_f64() < STREAM_INTERVAL {
+            return;
+        }
+
+        let Some(original_canvas_rect) = self.original_canvas_rect else {
+            return;
+        };
+
+        if self.current_line.len() <= self.streamed_len {
+            return;
+        }
+
+        self.last_stream = web_time::Instant::now();
+
+        let timestamp = self.current_timestamp();
+
+        let line = &self.current_line;
+
+        let operation = if self.streamed_len == 0 {
+            Operation::AddLine {
+                id: timestamp.id(),
+                line: line.clone(),
+                timestamp,
+            }
+        } else {
+            Operation::AppendPoints {
+                id: timestamp.id(),
+                timestamp,
+                stroke: line.stroke,
+                layer: line.layer,
+                offset: self.streamed_len,
+                points: line[self.streamed_len..].to_vec(),
+            }
+        };
+
+        self.streamed_len = line.len();
+
+        self.send(operation, &original_canvas_rect);
+    }
+
+    /// Spans the shape being drawn from where the drag started to `pos`.
+    ///
+    /// Shapes are only sent once they are finished, a click without dragging draws nothing.
+    fn drag_shape(&mut self, shape: Shape, pos: Pos2) {
+        let start = *self.shape_start.get_or_insert(pos);
+
+        if pos == start {
+            return;
+        }
+
+        self.current_line.stroke = self.stroke;
+        self.current_line.layer = self.active_layer;
+        self.current_line.shape = shape;
+        self.current_line.points = shape.points(start, pos, self.stroke.width);
+    }
+
+    /// Erases the lines within `radius` of `pos` in canvas coordinates, returns whether any were.
+    ///
+    /// Erased lines are removed, the pieces that are left of them come back as new lines.
+    fn erase_at(&mut self, pos: Pos2, radius: f32) -> bool {
+        let mut ids = Vec::new();
+        let mut pieces = Vec::new();
+
+        for (id, line) in self
+            .lines
+            .iter()
+            .filter(|(id, _)| self.lines.is_editable(id))
+        {
+            let Some(rest) = line.erase(pos, radius + line.stroke.width / 2.0) else {
+                continue;
+            };
+
+            ids.push(*id);
+
+            if self.precise_eraser {
+                let cut_from = Some(line.place(*id));
+
+                pieces.extend(rest.into_iter().map(|piece| Line { cut_from, ..piece }));
+            }
+        }
+
+        if ids.is_empty() {
+            return false;
+        }
+
+        log::info!("Removing lines: {:?}", ids);
+
+        let mut operations = vec![Operation::RemoveLines {
+            ids,
+            timestamp: self.clock.tick(),
+        }];
+
+        for line in pieces {
+            let timestamp = self.clock.tick();
+
+            operations.push(Operation::AddLine {
+                id: timestamp.id(),
+                line,
+                timestamp,
+            });
+        }
+
+        // A whole drag of the eraser is undone at once, see `release_eraser`.
+        self.history.extend(&operations, &self.lines);
+
+        for operation in operations {
+            self.commit(operation);
+        }
+
+        true
+    }
+
+    /// Ends a drag of the eraser, what it erased is undone in one step from now on.
+    fn release_eraser(&mut self) {
+        self.history.finish();
+    }
+
+    /// Starts typing text at `pos`, or edits the text there, finishing the text typed before.
+    fn edit_text_at(&mut self, pos: Pos2) {
+        self.finish_text();
+
+        let existing = selection::line_at(&self.lines, pos, 0.0)
+            .filter(|id| self.lines[id].shape == Shape::Text);
+
+        if existing.is_none() && !self.can_draw() {
+            return;
+        }
+
+        self.editing_text = Some(match existing {
+            Some(id) => EditedText {
+                id,
+                line: self.lines[&id].clone(),
+                focused: false,
+            },
+            None => EditedText {
+                id: self.clock.tick().id(),
+                line: Line {
+                    layer: self.active_layer,
+                    ..Line::new_text(pos, self.font_size, self.stroke.color, String::new())
+                },
+                focused: false,
+            },
+        });
+    }
+
+    /// Commits the text being typed, removing it if all of it was deleted.
+    fn finish_text(&mut self) {
+        let Some(EditedText { id, line, .. }) = self.editing_text.take() else {
+            return;
+        };
+
+        let timestamp = self.clock.tick();
+
+        match self.lines.get(&id) {
+            Some(_) if line.text.trim().is_empty() => {
+                self.perform(Operation::RemoveLines {
+                    ids: vec![id],
+                    timestamp,
+                });
+            }
+            Some(old) if *old == line => {}
+            _ if line.text.trim().is_empty() => {}
+            _ => {
+                log::info!("Sending text to backend");
+
+                self.perform(Operation::AddLine {
+                    id,
+                    line,
+                    timestamp,
+                });
+            }
+        }
+    }
+
+    /// Edits the text being typed in place, unrotated at its position on the canvas.
+    fn text_editor(&mut self, ui: &mut Ui, to_screen: emath::RectTransform) {
+        let Some(edited) = &mut self.editing_text else {
+            return;
+        };
+
+        let Some(layout) = edited.line.text_layout() else {
+            return;
+        };
+
+        let font_size = (layout.font_size * to_screen.scale().y).max(1.0);
+
+        let response = egui::Area::new(egui::Id::new("text_editor"))
+            .fixed_pos(to_screen * layout.pos)
+            .show(ui.ctx(), |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut edited.line.text)
+                        .font(egui::FontId::proportional(font_size))
+                        .text_color(edited.line.stroke.color)
+                        .hint_text("Type, click outside or press Escape to finish")
+                        .desired_rows(1)
+                        .frame(true),
+                )
+            })
+            .inner;
+
+        if !edited.focused {
+            response.request_focus();
+            edited.focused = true;
+        } else if !response.has_focus() {
+            self.finish_text();
+        }
+    }
+
+    /// Applies `operation` to the local lines and sends it to the backend.
+    fn commit(&mut self, operation: Operation) {
+        let Some(original_canvas_rect) = self.original_canvas_rect else {
+            return;
+        };
+
+        self.lines.apply(operation.clone());
+
+        self.send(operation, &original_canvas_rect);
+    }
+
+    /// Commits an action of the user that can be undone.
+    fn perform(&mut self, operation: Operation) {
+        self.history.record(&operation, &self.lines);
+
+        self.commit(operation);
+    }
+
+    /// Commits operations that are undone together as one action.
+    fn perform_all(&mut self, operations: Vec<Operation>) {
+        self.history.record_all(&operations, &self.lines);
+
+        for operation in operations {
+            self.commit(operation);
+        }
+    }
+
+    /// Drags the selection tool to `pos` in canvas coordinates, starting a drag if none is going on.
+    fn drag_selection(&mut self, pos: Pos2, radius: f32, modifiers: Modifiers) {
+        if self.selection.is_dragging() {
+            self.selection.drag(pos);
+        } else {
+            self.selection.press(&self.lines, pos, radius, modifiers);
+        }
+    }
+
+    /// Ends a drag of the selection tool, transforming the selected lines if they were dragged.
+    fn release_selection(&mut self) {
+        let Some(transform) = self.selection.release(&self.lines) else {
+            return;
+        };
+
+        let timestamp = self.clock.tick();
+
+        self.perform(Operation::Transform {
+            ids: self.selection.ids.iter().copied().collect(),
+            transform,
+            timestamp,
+        });
+    }
+
+    fn delete_selection(&mut self) {
+        if self.selection.ids.is_empty() {
+            return;
+        }
+
+        let ids: Vec<LineId> = std::mem::take(&mut self.selection.ids)
+            .into_iter()
+            .collect();
+
+        log::info!("Removing lines: {:?}", ids);
+
+        let timestamp = self.clock.tick();
+
+        self.perform(Operation::RemoveLines { ids, timestamp });
+    }
+
+    /// Adds copies of the selected lines next to them and selects the copies instead.
+    fn duplicate_selection(&mut self) {
+        let mut operations = Vec::new();
+
+        for id in &self.selection.ids {
+            let Some(line) = self.lines.get(id) else {
+                continue;
+            };
+
+            let mut line = line.clone();
+
+            for pos in line.iter_mut() {
+                *pos += vec2(DUPLICATE_OFFSET, DUPLICATE_OFFSET);
+            }
+
+            let timestamp = self.clock.tick();
+
+            operations.push(Operation::AddLine {
+                id: timestamp.id(),
+                line,
+                timestamp,
+            });
+        }
+
+        if operations.is_empty() {
+            return;
+        }
+
+        self.selection.ids = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::AddLine { id, .. } => Some(*id),
+                _ => None,
+            })
+            .collect();
+
+        self.perform_all(operations);
+    }
+
+    /// Draws the box and handles around the selected lines, or the lasso while one is drawn.
+    fn paint_selection(&self, painter: &egui::Painter, to_screen: emath::RectTransform) {
+        let stroke = painter.ctx().style().visuals.selection.stroke;
+
+        if let Some(lasso) = self.selection.lasso() {
+            let points = lasso.iter().map(|pos| to_screen * *pos).collect();
+
+            painter.add(egui::Shape::line(points, Stroke::new(1.0, stroke.color)));
+        }
+
+        let Some(bounds) = self.selection.bounds(&self.lines) else {
+            return;
+        };
+
+        let transform = self.selection.transform();
+
+        let corners = [
+            bounds.left_top(),
+            bounds.right_top(),
+            bounds.right_bottom(),
+            bounds.left_bottom(),
+        ];
+
+        let outline = corners
+            .iter()
+            .map(|corner| to_screen * transform.apply(*corner))
+            .collect();
+
+        painter.add(egui::Shape::closed_line(
+            outline,
+            Stroke::new(1.0, stroke.color),
+        ));
+
+        if self.selection.is_dragging() {
+            return;
+        }
+
+        let radius = PICK_RADIUS / to_screen.scale().x;
+
+        for (handle, pos) in selection::handles(bounds, radius) {
+            let pos = to_screen * pos;
+
+            match handle {
+                Handle::Rotate { .. } => {
+                    painter.line_segment(
+                        [pos, to_screen * bounds.center_top()],
+                        Stroke::new(1.0, stroke.color),
+                    );
+                    painter.circle(pos, PICK_RADIUS / 1.5, Color32::WHITE, stroke);
+                }
+                _ => {
+                    let square = Rect::from_center_size(pos, vec2(PICK_RADIUS, PICK_RADIUS) * 1.2);
+
+                    painter.rect(square, 0.0, Color32::WHITE, stroke);
+                }
+            }
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(operations) = self.history.undo() {
+            log::info!("Undo");
+
+            self.commit_again(operations);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(operations) = self.history.redo() {
+            log::info!("Redo");
+
+            self.commit_again(operations);
+        }
+    }
+
+    /// Commits operations from the history as new changes.
+    fn commit_again(&mut self, operations: Vec<Operation>) {
+        for mut operation in operations {
+            operation.set_timestamp(self.clock.tick());
+
+            self.commit(operation);
+        }
+    }
+
+    /// Sends `operation`, which is in canvas coordinates, to the backend.
+    fn send(&mut self, mut operation: Operation, original_canvas_rect: &Rect) {
+        operation.from_canvas(original_canvas_rect);
+
+        self.post(vec![operation]);
+    }
+
+    /// Posts operations in normalized coordinates, they stay pending until the backend confirms them.
+    fn post(&mut self, operations: Vec<Operation>) {
+        let post = self.pending.start(operations.clone());
+
+        let sender = self.sent_channel.sender.clone();
+
+        self.client.post_operations(
+            operations,
+            Box::new(move |result| {
+                sender.send((post, result)).ok();
+            }),
+        );
+    }
+
+    /// Takes in the results of requests, retries failed posts when due and clears the error
+    /// once the backend answers again.
+    fn update_sync_status(&mut self) {
+        let mut succeeded = false;
+
+        while let Ok((post, result)) = self.sent_channel.receiver.try_recv() {
+            match result {
+                Ok(()) => {
+                    self.pending.succeeded(post);
+                    self.status.succeeded(None);
+                    succeeded = true;
+                }
+                Err(error) if error.is_permanent() => {
+                    log::error!("Operations rejected: {}", error);
+
+                    let dropped = self.pending.rejected(post)
+                        + self.rejected.take().map_or(0, |(dropped, _)| dropped);
+
+                    self.rejected = Some((dropped, error));
+                }
+                Err(error) => {
+                    log::error!("Error: {}", error);
+
+                    self.pending.failed(post);
+                    self.status.failed();
+                    self.sync_error = Some(error);
+                }
+            }
+        }
+
+        while let Ok(outcome) = self.outcome_channel.receiver.try_recv() {
+            match outcome {
+                Ok(rtt) => {
+                    self.status.succeeded(Some(rtt));
+                    succeeded = true;
+                }
+                Err(error) => {
+                    self.status.failed();
+                    self.sync_error = Some(error);
+                }
+            }
+        }
+
+        if succeeded && !self.pending.has_failed() {
+            self.sync_error = None;
+        }
+
+        if let Some(posts) = self.pending.due() {
+            for operations in posts {
+                log::info!("Retrying {} operations", operations.len());
+
+                self.post(operations);
+            }
+        }
+    }
+
+    /// Sends the failed operations again and refreshes the board and presence right away.
+    fn retry(&mut self) {
+        self.sync_error = None;
+
+        self.pending.retry_now();
+
+        if let Some(posts) = self.pending.due() {
+            for operations in posts {
+                self.post(operations);
+            }
+        }
+
+        if let Some(original_canvas_rect) = self.original_canvas_rect {
+            self.request_lines(original_canvas_rect);
+        }
+
+        self.say_hello();
+    }
+
+    fn status_bar(&mut self, ctx: &egui::Context) {
+        let rejected = self.rejected.as_ref().map(|(dropped, error)| {
+            format!("{} changes were refused and dropped: {}", dropped, error)
+        });
+
+        let message = self.sync_error.as_ref().map(ToString::to_string);
+
+        if rejected.is_none() && message.is_none() {
+            return;
+        }
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            if let Some(rejected) = rejected {
+                ui.horizontal(|ui| {
+                    ui.colored_label(ui.visuals().error_fg_color, rejected);
+
+                    if ui.button("Dismiss").clicked() {
+                        self.rejected = None;
+                    }
+                });
+            }
+
+            let Some(message) = message else {
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ui.colored_label(ui.visuals().error_fg_color, message);
+
+                if let Some(wait) = self.pending.next_retry_in() {
+                    ui.label(format!(
+                        "{} changes not sent, retrying in {}s",
+                        self.pending.len(),
+                        wait.as_secs_f32().ceil()
+                    ));
+                }
+
+                if ui.button("Retry").clicked() {
+                    self.retry();
+                }
+            });
+        });
+    }
+
+    /// Connection state in the menu bar, opening details about the sync when clicked.
+    fn sync_status(&mut self, ui: &mut Ui) {
+        let connection = self.status.connection(self.socket_open);
+
+        let mut label = format!("● {}", connection.label());
+
+        if self.pending.len() > 0 {
+            label = format!("{} · {} pending", label, self.pending.len());
+        }
+
+        let text = egui::RichText::new(label).color(connection.color());
+
+        ui.menu_button(text, |ui| {
+            egui::Grid::new("sync_status")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Connection");
+                    ui.label(connection.label());
+                    ui.end_row();
+
+                    ui.label("Live updates");
+                    ui.label(if self.socket_open {
+                        "Websocket"
+                    } else {
+                        "Polling"
+                    });
+                    ui.end_row();
+
+                    ui.label("Last sync");
+                    ui.label(match self.status.since_last_sync() {
+                        Some(elapsed) => format!("{}s ago", elapsed.as_secs()),
+                        None => "Never".to_string(),
+                    });
+                    ui.end_row();
+
+                    ui.label("Latency");
+                    ui.label(match self.status.rtt() {
+                        Some(rtt) => format!("{} ms", rtt.as_millis()),
+                        None => "Unknown".to_string(),
+                    });
+                    ui.end_row();
+
+                    ui.label("Pending changes");
+                    ui.label(self.pending.len().to_string());
+                    ui.end_row();
+                });
+
+            if let Some(error) = &self.sync_error {
+                ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+            }
+        })
+        .response
+        .on_hover_text("Connection to the backend");
+    }
+
+    /// Key under which the pending operations of the board are stored.
+    fn pending_key(&self) -> String {
+        format!("pending_operations/{}", self.settings.board_id)
+    }
+
+    fn clock_key(&self) -> String {
+        format!("clock/{}", self.settings.board_id)
+    }
+
+    /// Opens the export of the board with the current background in a new tab.
+    fn open_export(&self, ctx: &egui::Context, format: &str) {
+        let background = self.texture_handles[&self.current_background_id].name();
+
+        let url = format!(
+            "{}?background={}",
+            self.board_url(&format!("export.{}", format)),
+            background
+        );
+
+        ctx.open_url(egui::OpenUrl::new_tab(url));
+    }
+
+    fn board_url(&self, endpoint: &str) -> String {
+        self.settings.board_url(endpoint)
+    }
+}
+
+impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, &self.pending_key(), &self.pending.operations());
+        eframe::set_value(storage, &self.clock_key(), &self.clock.latest());
+    }
+
+    /// Often enough that few changes are lost if the app is killed while offline.
+    fn auto_save_interval(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let board_created = self.update_board();
+
+        if let Some(original_canvas_rect) = self.original_canvas_rect.filter(|_| board_created) {
+            self.update_socket(ctx, original_canvas_rect);
+        }
+
+        if !self.socket_open && self.last_update.elapsed().as_secs_f64() > UPDATE_FREQUENCY {
+            if let Some(original_canvas_rect) = self.original_canvas_rect {
+                self.request_lines(original_canvas_rect);
+            }
+
+            self.last_update = web_time::Instant::now();
+        }
+
+        if let Ok((since, delta)) = self.new_lines_channel.receiver.try_recv() {
+            self.merge_delta(since, delta);
+        }
+
+        if board_created {
+            self.update_presence();
+        }
+
+        self.update_sync_status();
+
+        // Text fields undo their own typing while they are focused.
+        if !ctx.wants_keyboard_input() {
+            // Shift is checked first, `consume_shortcut` also matches Ctrl+Shift+Z for Ctrl+Z.
+            if ctx.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT)) {
+                self.redo();
+            }
+
+            if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+                self.undo();
+            }
+        }
+
+        if self.tool == Tool::Select {
+            self.selection.retain(&self.lines);
+
+            // Keys go to the name field while it is focused.
+            if !ctx.wants_keyboard_input() {
+                if ctx.input_mut(|i| {
+                    i.consume_key(Modifiers::NONE, Key::Delete)
+                        || i.consume_key(Modifiers::NONE, Key::Backspace)
+                }) {
+                    self.delete_selection();
+                }
+
+                if ctx.input_mut(|i| i.consume_shortcut(&DUPLICATE_SHORTCUT)) {
+                    self.duplicate_selection();
+                }
+
+                if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+                    self.selection.clear();
+                }
+            }
+        } else {
+            self.selection.clear();
+        }
+
+        if self.tool != Tool::Text {
+            self.finish_text();
+        }
+
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                egui::widgets::global_theme_preference_buttons(ui);
+
+                ui.label(format!("Board: {}", self.settings.board_id));
+
+                self.sync_status(ui);
+
+                ui.toggle_value(
+                    &mut self.show_presence,
+                    format!("People: {}", self.participants.len()),
+                )
+                .on_hover_text("Show who is on the board");
+
+                ui.toggle_value(&mut self.show_layers, "Layers")
+                    .on_hover_text("Show the layers of the board");
+
+                ComboBox::from_id_salt("Images").show_ui(ui, |ui| {
+                    for (id, handle) in self.texture_handles.iter() {
+                        let name = handle.name().split('.').next().unwrap().to_string();
+
+                        if ui
+                            .selectable_value(&mut self.current_background_id, *id, &name)
+                            .clicked()
+                        {
+                            log::info!("Selected image: {}", name);
+                        }
+                    }
+                });
+
+                for shape in Shape::ALL {
+                    ui.selectable_value(&mut self.tool, Tool::Draw(shape), shape.name())
+                        .on_hover_text("Draw with the primary button, shapes are spanned by dragging");
+                }
+
+                ui.selectable_value(&mut self.tool, Tool::Text, "Text")
+                    .on_hover_text("Click to add text, or click text to edit it");
+
+                if self.tool == Tool::Text {
+                    ui.add(
+                        egui::DragValue::new(&mut self.font_size)
+                            .range(8.0..=96.0)
+                            .suffix(" px"),
+                    )
+                    .on_hover_text("Font size of new text");
+                }
+
+                ui.selectable_value(&mut self.tool, Tool::Select, "Select").on_hover_text(
+                    "Pick lines to move, scale or rotate them, Shift adds to the selection and Alt draws a lasso",
+                );
+
+                ui.checkbox(&mut self.precise_eraser, "Precise eraser").on_hover_text(
+                    "The secondary button erases only the touched part of lines instead of whole lines",
+                );
+
+                if !self.selection.ids.is_empty() {
+                    if ui
+                        .button("Duplicate")
+                        .on_hover_text(ctx.format_shortcut(&DUPLICATE_SHORTCUT))
+                        .clicked()
+                    {
+                        self.duplicate_selection();
+                    }
+
+                    if ui.button("Delete").on_hover_text("Delete or Backspace").clicked() {
+                        self.delete_selection();
+                    }
+
+                    if ui
+                        .add_enabled(self.can_draw(), egui::Button::new("Move to layer"))
+                        .on_hover_text("Move the selected lines to the layer that is drawn on")
+                        .clicked()
+                    {
+                        self.move_selection_to_active_layer();
+                    }
+                }
+
+                let color = self.stroke.color;
+
+                ui.add(&mut self.stroke);
+
+                if self.stroke.color != color {
+                    self.custom_color = true;
+                }
+
+                if let Some(assigned_color) = self.assigned_color.filter(|_| self.custom_color) {
+                    if ui
+                        .button("Reset color")
+                        .on_hover_text("Draw with the color the others see you with")
+                        .clicked()
+                    {
+                        self.stroke.color = assigned_color;
+                        self.custom_color = false;
+                    }
+                }
+
+                ui.add(egui::Slider::new(&mut self.scroll_speed, 1.0..=20.0).text("Scroll speed"));
+
+                if ui
+                    .add_enabled(self.history.can_undo(), egui::Button::new("Undo"))
+                    .on_hover_text(ctx.format_shortcut(&UNDO_SHORTCUT))
+                    .clicked()
+                {
+                    self.undo();
+                }
+
+                if ui
+                    .add_enabled(self.history.can_redo(), egui::Button::new("Redo"))
+                    .on_hover_text(ctx.format_shortcut(&REDO_SHORTCUT))
+                    .clicked()
+                {
+                    self.redo();
+                }
+
+                if ui
+                    .button("Export SVG")
+                    .on_hover_text("Download the board as SVG")
+                    .clicked()
+                {
+                    self.open_export(ctx, "svg");
+                }
+
+                if ui
+                    .button("Export PNG")
+                    .on_hover_text("Download the board as PNG")
+                    .clicked()
+                {
+                    self.open_export(ctx, "png");
+                }
+
+                ui.button("Clear")
+                    .on_hover_text("Clear the canvas, except for hidden and locked layers")
+                    .clicked()
+                    .then(|| self.clear())
+            });
+        });
+
+        self.status_bar(ctx);
+
+        if self.show_presence {
+            egui::SidePanel::right("presence_panel")
+                .resizable(false)
+                .show(ctx, |ui| self.presence_panel(ui));
+        }
+
+        if self.show_layers {
+            egui::SidePanel::left("layers_panel")
+                .resizable(false)
+                .show(ctx, |ui| self.layers_panel(ui));
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            Frame::canvas(ui.style()).show(ui, |ui| {
+                let (mut response, painter) =
+                    ui.allocate_painter(ui.available_size_before_wrap(), Sense::drag());
+
+                let original_background_size = self
+                    .texture_handles
+                    .get(&self.current_background_id)
+                    .unwrap()
+                    .size();
+
+                let background_size = original_background_size
+                    .iter()
+                    .map(|x| (*x as f32))
+                    .collect::<Vec<f32>>();
+
+                let offset_x = self.background_offset.x;
+                let offset_y = self.background_offset.y;
+
+                let shrink_x = background_size[0] * self.zoom;
+                let shrink_y = background_size[1] * self.zoom;
+
+                let shrink = emath::vec2(shrink_x, shrink_y);
+
+                let background_rect = Rect::from_min_size(
+                    Pos2 {
+                        x: offset_x,
+                        y: offset_y,
+                    },
+                    emath::vec2(background_size[0], background_size[1]),
+                )
+                .shrink2(shrink);
+
+                let canvas_size = ui.available_size_before_wrap();
+
+                if self.original_canvas_rect.is_none() {
+                    self.original_canvas_rect = Some(response.rect);
+
+                    debug!("Original canvas rect: {:?}", self.original_canvas_rect);
+                    debug!("Background rect: {:?}", background_rect);
+                    debug!("Canvas size: {:?}", canvas_size);
+                    debug!("Background size: {:?}", background_size);
+                }
+
+                painter.image(
+                    self.current_background_id,
+                    background_rect,
+                    Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                    Color32::WHITE,
+                );
+
+                let from_screen = emath::RectTransform::from_to(
+                    background_rect,
+                    self.original_canvas_rect.unwrap(),
+                );
+
+                let to_screen = from_screen.inverse();
+
+                let which_mouse_button_down = response.ctx.input(|i| {
+                    if i.pointer.primary_down() {
+                        MouseDown::Primary
+                    } else if i.pointer.secondary_down() {
+                        MouseDown::Secondary
+                    } else if i.pointer.middle_down() {
+                        MouseDown::Middle
+                    } else {
+                        MouseDown::None
+                    }
+                });
+
+                let scroll_delta = response.ctx.input(|i| i.raw_scroll_delta);
+
+                let scroll_delta_y = scroll_delta.y * self.scroll_speed * 1e-4;
+
+                if scroll_delta_y != 0.0 {
+                    self.zoom = (self.zoom - scroll_delta_y).clamp(-2.0, 2.0);
+                }
+
+                self.publish_cursor(response.hover_pos().map(|pos| from_screen * pos));
+
+                match response.interact_pointer_pos() {
+                    Some(pointer_pos) => {
+                        let canvas_pos = from_screen * pointer_pos;
+
+                        match which_mouse_button_down {
+                            MouseDown::Primary if self.tool == Tool::Select => {
+                                let modifiers = response.ctx.input(|i| i.modifiers);
+
+                                self.drag_selection(
+                                    canvas_pos,
+                                    PICK_RADIUS * from_screen.scale().x,
+                                    modifiers,
+                                );
+
+                                response.mark_changed();
+                            }
+                            MouseDown::Primary if self.tool == Tool::Text => {
+                                if response.ctx.input(|i| i.pointer.primary_pressed()) {
+                                    self.edit_text_at(canvas_pos);
+
+                                    response.mark_changed();
+                                }
+                            }
+                            MouseDown::Primary
+                                if matches!(self.tool, Tool::Draw(_)) && !self.can_draw() => {}
+                            MouseDown::Primary if self.tool == Tool::Draw(Shape::Freehand) => {
+                                if self.current_line.is_empty() {
+                                    self.current_line.stroke = self.stroke;
+                                    self.current_line.layer = self.active_layer;
+                                }
+
+                                if self.current_line.last() != Some(&canvas_pos) {
+                                    self.current_line.push(canvas_pos);
+                                    response.mark_changed();
+                                }
+
+                                self.stream_current_line();
+                            }
+                            MouseDown::Primary => {
+                                if let Tool::Draw(shape) = self.tool {
+                                    self.drag_shape(shape, canvas_pos);
+
+                                    response.mark_changed();
+                                }
+                            }
+                            MouseDown::Secondary => {
+                                if self.erase_at(canvas_pos, ERASER_RADIUS * from_screen.scale().x)
+                                {
+                                    response.mark_changed();
+                                }
+                            }
+                            MouseDown::Middle => {
+                                let drag_delta = response.drag_delta();
+
+                                self.background_offset = self.background_offset.add(drag_delta);
+                            }
+                            MouseDown::None => (),
+                        }
+                    }
+                    None => {
+                        if self.selection.is_dragging() {
+                            self.release_selection();
+
+                            response.mark_changed();
+                        }
+
+                        self.release_eraser();
+
+                        self.shape_start = None;
+
+                        if !self.current_line.is_empty() {
+                            log::info!("Sending line to backend");
+
+                            let timestamp = self.current_timestamp();
+
+                            // Sent again in full so collaborators that missed a chunk get all of it.
+                            self.perform(Operation::AddLine {
+                                id: timestamp.id(),
+                                line: self.current_line.clone(),
+                                timestamp,
+                            });
+
+                            self.current_line = Line::default();
+                            self.current_timestamp = None;
+                            self.streamed_len = 0;
+
+                            response.mark_changed();
+                        }
+                    }
+                }
+
+                // The streamed part of the current line comes back from the backend, it is drawn from `current_line`.
+                // Selected lines are drawn where the drag of the selection tool would put them.
+                let selection_transform = self.selection.transform();
+
+                let current_id = self.current_timestamp.map(Timestamp::id);
+
+                let shapes = self
+                    .lines
+                    .drawing_order()
+                    .into_iter()
+                    .filter(|(id, _)| Some(*id) != current_id)
+                    .filter(|(id, _)| {
+                        self.editing_text.as_ref().map(|edited| edited.id) != Some(*id)
+                    })
+                    .map(|(id, line)| match self.selection.ids.contains(&id) {
+                        true => (selection_transform, line),
+                        false => (Transform::IDENTITY, line),
+                    })
+                    .chain(std::iter::once((Transform::IDENTITY, &self.current_line)))
+                    .filter(|(_, line)| line.len() >= 2)
+                    .flat_map(|(transform, line)| {
+                        let mut line = line.clone();
+
+                        for pos in line.iter_mut() {
+                            *pos = to_screen * transform.apply(*pos);
+                        }
+
+                        match line.text_layout() {
+                            Some(layout) => vec![text_shape(&painter, &line, layout)],
+                            None => line
+                                .paths()
+                                .into_iter()
+                                .map(|path| egui::Shape::line(path, line.stroke))
+                                .collect(),
+                        }
+                    });
+
+                painter.extend(shapes.collect::<Vec<_>>());
+
+                self.text_editor(ui, to_screen);
+
+                if self.tool == Tool::Select {
+                    self.paint_selection(&painter, to_screen);
+                }
+
+                self.paint_cursors(&painter, to_screen);
+
+                response
+            });
+        });
+
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+}
 
 pub enum MouseDown {
     None,
@@ -474,6 +1975,19 @@ pub enum MouseDown {
     Middle,
 }
 
+/// Text laid out in a single galley, `line` is in screen coordinates.
+fn text_shape(painter: &egui::Painter, line: &Line, layout: TextLayout) -> egui::Shape {
+    let galley = painter.layout_no_wrap(
+        line.text.clone(),
+        egui::FontId::proportional(layout.font_size.max(1.0)),
+        line.stroke.color,
+    );
+
+    egui::epaint::TextShape::new(layout.pos, galley, line.stroke.color)
+        .with_angle(layout.angle)
+        .into()
+}
+
 fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageError> {
     let image = image::load_from_memory(image_data)?;
     let size = [image.width() as _, image.height() as _];
@@ -482,8 +1996,643 @@ fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageE
     Ok(ColorImage::from_rgba_unmultiplied(size, pixels.as_slice()))
 }
 
+fn unix_millis() -> u64 {
+    web_time::SystemTime::now()
+        .duration_since(web_time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 fn get_random_u64() -> u64 {
     let mut buffer = [0u8; 8];
     getrandom(&mut buffer).unwrap();
     u64::from_ne_bytes(buffer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::FakeClient;
+    use crate::pending::MAX_POST_OPERATIONS;
+    use crate::status::Connection;
+
+    const ID: LineId = LineId {
+        counter: 1,
+        client: 7,
+    };
+
+    fn app_with(client: &FakeClient) -> App {
+        let mut app = App::with_client(
+            &egui::Context::default(),
+            Settings::new("http://localhost:8432", "test"),
+            Box::new(client.clone()),
+        );
+
+        app.original_canvas_rect = Some(Rect::from_min_size(Pos2::ZERO, vec2(100.0, 100.0)));
+
+        app
+    }
+
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn line(points: &[Pos2]) -> Line {
+        let mut line = Line::new(Stroke::new(1.0, Color32::RED));
+        line.extend_from_slice(points);
+        line
+    }
+
+    #[test]
+    fn fetched_lines_are_merged_in_canvas_coordinates() {
+        let client = FakeClient::new();
+
+        client.apply(vec![Operation::AddLine {
+            id: ID,
+            line: line(&[pos2(0.1, 0.2), pos2(0.5, 0.5)]),
+            timestamp: Timestamp {
+                counter: 1,
+                client: 7,
+            },
+        }]);
+
+        let mut app = app_with(&client);
+
+        app.request_lines(app.original_canvas_rect.unwrap());
+
+        let (since, delta) = app.new_lines_channel.receiver.try_recv().unwrap();
+        app.merge_delta(since, delta);
+
+        assert_eq!(app.revision, 1);
+        assert_eq!(app.lines[&ID][..], [pos2(10.0, 20.0), pos2(50.0, 50.0)]);
+    }
+
+    #[test]
+    fn hello_assigns_a_session_and_lists_participants() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+        app.name = "  Ada  ".to_string();
+
+        app.say_hello();
+
+        let presence = app.presence_channel.receiver.try_recv().unwrap();
+
+        assert_eq!(presence.session, 1);
+        assert_eq!(presence.participants[0].name, "Ada");
+        assert_eq!(client.participants().len(), 1);
+    }
+
+    #[test]
+    fn assigned_color_is_used_unless_the_user_picked_one() {
+        let client = FakeClient::new();
+
+        let mut other = app_with(&client);
+        other.say_hello();
+
+        let mut app = app_with(&client);
+        app.say_hello();
+
+        let presence = app.presence_channel.receiver.try_recv().unwrap();
+        let color = presence.color;
+
+        assert_ne!(color, client.participants()[0].color);
+
+        app.stroke.color = Color32::BLACK;
+        app.custom_color = true;
+        app.presence_channel.sender.send(presence).unwrap();
+        app.update_presence();
+
+        assert_eq!(app.assigned_color, Some(color));
+        assert_eq!(app.stroke.color, Color32::BLACK);
+    }
+
+    #[test]
+    fn failed_operations_are_sent_again_on_retry() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        client.set_offline(true);
+
+        let timestamp = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: ID,
+            line: line(&[pos2(10.0, 10.0), pos2(20.0, 20.0)]),
+            timestamp,
+        });
+
+        app.update_sync_status();
+
+        assert!(matches!(app.sync_error, Some(TransportError::Network(_))));
+        assert_eq!(app.pending.len(), 1);
+        assert!(app.pending.next_retry_in().is_some());
+        assert!(client.operations().is_empty());
+
+        client.set_offline(false);
+
+        app.retry();
+        app.update_sync_status();
+
+        assert_eq!(app.sync_error, None);
+        assert_eq!(app.pending.len(), 0);
+        assert!(client.lines().contains_key(&ID));
+    }
+
+    #[test]
+    fn connection_goes_offline_after_failed_requests_and_recovers() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        app.say_hello();
+        app.update_sync_status();
+
+        assert_eq!(app.status.connection(true), Connection::Connected);
+        assert_eq!(app.status.connection(false), Connection::Reconnecting);
+        assert!(app.status.rtt().is_some());
+
+        client.set_offline(true);
+
+        for _ in 0..3 {
+            app.say_hello();
+            app.update_sync_status();
+        }
+
+        assert_eq!(app.status.connection(true), Connection::Offline);
+
+        client.set_offline(false);
+
+        app.say_hello();
+        app.update_sync_status();
+
+        assert_eq!(app.status.connection(true), Connection::Connected);
+    }
+
+    #[test]
+    fn restored_operations_are_sent_right_away() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        app.pending = Pending::restore(vec![Operation::Clear {
+            timestamp: Timestamp {
+                counter: 5,
+                client: 1,
+            },
+        }]);
+
+        app.update_sync_status();
+        app.update_sync_status();
+
+        assert_eq!(client.operations().len(), 1);
+        assert_eq!(app.pending.len(), 0);
+    }
+
+    #[test]
+    fn retries_are_split_and_refused_operations_are_dropped() {
+        let client = FakeClient::new();
+        client.set_max_operations(MAX_POST_OPERATIONS);
+
+        let mut app = app_with(&client);
+
+        let operations = (1..=2 * MAX_POST_OPERATIONS as u64 + 1)
+            .map(|counter| Operation::RemoveLines {
+                ids: vec![Timestamp { counter, client: 1 }.id()],
+                timestamp: Timestamp { counter, client: 1 },
+            })
+            .collect();
+
+        app.pending = Pending::restore(operations);
+
+        app.update_sync_status();
+        app.update_sync_status();
+
+        assert_eq!(client.operations().len(), 2 * MAX_POST_OPERATIONS + 1);
+        assert_eq!(app.pending.len(), 0);
+
+        client.set_max_operations(0);
+
+        let timestamp = app.clock.tick();
+
+        app.perform(Operation::Clear { timestamp });
+        app.update_sync_status();
+
+        assert_eq!(app.pending.len(), 0);
+        assert_eq!(app.pending.next_retry_in(), None);
+        assert!(matches!(
+            app.rejected,
+            Some((1, TransportError::Status { code: 413, .. }))
+        ));
+    }
+
+    #[test]
+    fn undo_posts_the_inverse_operation() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        let timestamp = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: ID,
+            line: line(&[pos2(10.0, 10.0), pos2(20.0, 20.0)]),
+            timestamp,
+        });
+
+        assert!(client.lines().contains_key(&ID));
+
+        app.undo();
+
+        assert!(matches!(
+            &client.operations()[..],
+            [Operation::AddLine { .. }, Operation::RemoveLines { ids, .. }] if ids == &[ID]
+        ));
+        assert!(client.lines().is_empty());
+    }
+
+    #[test]
+    fn erasing_splits_lines_and_undo_restores_them() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        let timestamp = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: ID,
+            line: line(&[pos2(10.0, 50.0), pos2(90.0, 50.0)]),
+            timestamp,
+        });
+
+        assert!(app.erase_at(pos2(50.0, 50.0), 5.0));
+        assert!(!app.lines.contains_key(&ID));
+        assert_eq!(app.lines.len(), 2);
+        assert_eq!(client.lines().len(), 2);
+
+        app.undo();
+
+        assert_eq!(client.lines().keys().collect::<Vec<_>>(), [&ID]);
+
+        app.precise_eraser = false;
+
+        assert!(app.erase_at(pos2(50.0, 50.0), 5.0));
+        assert!(client.lines().is_empty());
+    }
+
+    #[test]
+    fn a_drag_of_the_eraser_is_undone_in_one_step() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        for y in [30.0, 60.0] {
+            let timestamp = app.clock.tick();
+
+            app.perform(Operation::AddLine {
+                id: timestamp.id(),
+                line: line(&[pos2(10.0, y), pos2(90.0, y)]),
+                timestamp,
+            });
+        }
+
+        let drawn: Vec<LineId> = client.lines().keys().copied().collect();
+
+        for y in [25.0, 30.0, 35.0, 60.0] {
+            app.erase_at(pos2(50.0, y), 5.0);
+        }
+
+        app.release_eraser();
+
+        assert_eq!(client.lines().len(), 4);
+
+        app.undo();
+
+        assert_eq!(client.lines().keys().copied().collect::<Vec<_>>(), drawn);
+
+        app.undo();
+
+        assert_eq!(client.lines().len(), 1);
+    }
+
+    #[test]
+    fn text_is_added_edited_and_restored_on_undo() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+        app.tool = Tool::Text;
+
+        app.edit_text_at(pos2(10.0, 10.0));
+        app.editing_text.as_mut().unwrap().line.text = "Label".to_string();
+        app.finish_text();
+
+        let (id, line) = app.lines.iter().next().unwrap();
+        let id = *id;
+
+        assert_eq!(line.text, "Label");
+        assert_eq!(client.lines()[&id].text, "Label");
+
+        app.edit_text_at(pos2(12.0, 15.0));
+        app.editing_text.as_mut().unwrap().line.text = "Renamed".to_string();
+        app.finish_text();
+
+        assert_eq!(client.lines()[&id].text, "Renamed");
+
+        app.undo();
+
+        assert_eq!(client.lines()[&id].text, "Label");
+    }
+
+    #[test]
+    fn selected_lines_are_moved_and_duplicated() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+        app.tool = Tool::Select;
+
+        let timestamp = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: ID,
+            line: line(&[pos2(10.0, 10.0), pos2(20.0, 20.0)]),
+            timestamp,
+        });
+
+        app.drag_selection(pos2(15.0, 15.0), PICK_RADIUS, Modifiers::NONE);
+        app.drag_selection(pos2(45.0, 15.0), PICK_RADIUS, Modifiers::NONE);
+        app.release_selection();
+
+        assert!(app.selection.ids.contains(&ID));
+        assert_eq!(app.lines[&ID][..], [pos2(40.0, 10.0), pos2(50.0, 20.0)]);
+        assert!(matches!(
+            client.operations().last(),
+            Some(Operation::Transform { ids, .. }) if ids == &[ID]
+        ));
+
+        app.duplicate_selection();
+
+        assert_eq!(app.lines.len(), 2);
+        assert_eq!(client.lines().len(), 2);
+        assert!(!app.selection.ids.contains(&ID));
+
+        app.undo();
+
+        assert_eq!(client.lines().keys().collect::<Vec<_>>(), [&ID]);
+    }
+
+    #[test]
+    fn lines_move_between_layers_and_locked_layers_are_left_alone() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        let timestamp = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: ID,
+            line: line(&[pos2(10.0, 50.0), pos2(90.0, 50.0)]),
+            timestamp,
+        });
+
+        app.add_layer();
+
+        let layers = client.lines().layers();
+
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[1].0, app.active_layer);
+
+        app.selection.ids.insert(ID);
+        app.move_selection_to_active_layer();
+
+        assert_eq!(client.lines()[&ID].layer, app.active_layer);
+
+        app.undo();
+
+        assert_eq!(client.lines()[&ID].layer, DEFAULT_LAYER);
+
+        app.set_layer(
+            DEFAULT_LAYER,
+            Layer {
+                locked: true,
+                ..Layer::default()
+            },
+        );
+
+        assert!(!app.erase_at(pos2(50.0, 50.0), 5.0));
+        assert_eq!(selection::line_at(&app.lines, pos2(50.0, 50.0), 5.0), None);
+
+        app.active_layer = DEFAULT_LAYER;
+
+        assert!(!app.can_draw());
+
+        app.undo();
+
+        assert!(app.can_draw());
+        assert!(app.erase_at(pos2(50.0, 50.0), 5.0));
+    }
+
+    #[test]
+    fn clearing_leaves_locked_layers_alone() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        let kept = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: kept.id(),
+            line: line(&[pos2(10.0, 50.0), pos2(90.0, 50.0)]),
+            timestamp: kept,
+        });
+
+        app.set_layer(
+            DEFAULT_LAYER,
+            Layer {
+                locked: true,
+                ..Layer::default()
+            },
+        );
+
+        app.add_layer();
+
+        let cleared = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: cleared.id(),
+            line: Line {
+                layer: app.active_layer,
+                ..line(&[pos2(10.0, 10.0), pos2(90.0, 10.0)])
+            },
+            timestamp: cleared,
+        });
+
+        app.clear();
+
+        assert_eq!(client.lines().keys().collect::<Vec<_>>(), [&kept.id()]);
+
+        app.set_layer(DEFAULT_LAYER, Layer::default());
+        app.clear();
+
+        assert!(client.lines().is_empty());
+        assert!(matches!(
+            client.operations().last(),
+            Some(Operation::Clear { .. })
+        ));
+    }
+
+    #[test]
+    fn erased_and_moved_lines_keep_their_place() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        let draw = |app: &mut App, points: &[Pos2]| {
+            let timestamp = app.clock.tick();
+
+            app.perform(Operation::AddLine {
+                id: timestamp.id(),
+                line: Line {
+                    layer: app.active_layer,
+                    ..line(points)
+                },
+                timestamp,
+            });
+
+            timestamp.id()
+        };
+
+        let cut = draw(&mut app, &[pos2(10.0, 50.0), pos2(90.0, 50.0)]);
+        let above = draw(&mut app, &[pos2(80.0, 10.0), pos2(80.0, 90.0)]);
+
+        assert!(app.erase_at(pos2(50.0, 50.0), 5.0));
+
+        let drawn: Vec<_> = app.lines.drawing_order().into_iter().collect();
+
+        assert_eq!(drawn.len(), 3);
+        assert!(drawn[..2]
+            .iter()
+            .all(|(_, line)| line.cut_from == Some(cut)));
+        assert_eq!(drawn[2].0, above);
+
+        app.add_layer();
+
+        let newer = draw(&mut app, &[pos2(10.0, 10.0), pos2(20.0, 20.0)]);
+
+        app.selection.ids.insert(above);
+        app.move_selection_to_active_layer();
+
+        let drawn: Vec<_> = app
+            .lines
+            .drawing_order()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+
+        assert_eq!(drawn[2..], [above, newer]);
+    }
+
+    #[test]
+    fn full_deltas_are_merged_unless_the_board_was_created_again() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        app.request_lines(app.original_canvas_rect.unwrap());
+
+        let timestamp = app.clock.tick();
+
+        app.perform(Operation::AddLine {
+            id: timestamp.id(),
+            line: line(&[pos2(10.0, 20.0), pos2(50.0, 50.0)]),
+            timestamp,
+        });
+
+        // Taken before the line was drawn.
+        let (since, delta) = app.new_lines_channel.receiver.try_recv().unwrap();
+        app.merge_delta(since, delta);
+
+        assert!(app.lines.contains_key(&timestamp.id()));
+        assert_eq!(app.revision, 0);
+
+        app.request_lines(app.original_canvas_rect.unwrap());
+
+        let (since, delta) = app.new_lines_channel.receiver.try_recv().unwrap();
+        app.merge_delta(since, delta);
+
+        assert_eq!(app.revision, 1);
+
+        let recreated = FakeClient::new();
+        app.client = Box::new(recreated);
+
+        app.request_lines(app.original_canvas_rect.unwrap());
+
+        let (since, delta) = app.new_lines_channel.receiver.try_recv().unwrap();
+        app.merge_delta(since, delta);
+
+        assert!(app.lines.is_empty());
+        assert_eq!(app.revision, 0);
+    }
+
+    #[test]
+    fn the_board_is_created_before_saying_hello() {
+        let client = FakeClient::new();
+        client.set_offline(true);
+
+        let mut app = app_with(&client);
+
+        assert!(!app.update_board());
+        assert!(!app.update_board());
+
+        client.set_offline(false);
+        app.last_create_attempt = None;
+
+        assert!(!app.update_board());
+        assert!(app.update_board());
+    }
+
+    #[test]
+    fn the_clock_continues_where_the_last_session_left_off() {
+        let client = FakeClient::new();
+
+        let mut app = app_with(&client);
+
+        for _ in 0..5 {
+            app.clock.tick();
+        }
+
+        let mut storage = MemoryStorage::default();
+        eframe::App::save(&mut app, &mut storage);
+
+        let mut restarted = app_with(&client);
+        restarted.load(&storage);
+
+        assert_eq!(restarted.clock.tick().counter, 6);
+
+        let pending = vec![Operation::RemoveLines {
+            ids: vec![ID],
+            timestamp: Timestamp {
+                counter: 9,
+                client: 3,
+            },
+        }];
+        eframe::set_value(&mut storage, &app.pending_key(), &pending);
+
+        let mut restarted = app_with(&client);
+        restarted.load(&storage);
+
+        assert_eq!(restarted.pending.operations(), pending);
+        assert_eq!(restarted.clock.tick().counter, 10);
+    }
+}
@@ -0,0 +1,33 @@
+//! Typed access to the HTTP routes of a board, so `App` does not depend on how requests are made.
+
+mod fake;
+mod http;
+
+use shared::{Delta, Hello, Operation, Presence};
+
+use crate::requests::TransportError;
+
+pub use fake::FakeClient;
+pub use http::HttpClient;
+
+/// Called with the result of a request once it is done.
+///
+/// The fake client calls it right away, the HTTP client from wherever the response arrives.
+pub type Callback<T> = Box<dyn FnOnce(Result<T, TransportError>) + Send>;
+
+/// Routes of the board the app is drawing on.
+pub trait BackendClient {
+    /// Creates the board unless it exists already, the other routes only know existing boards.
+    fn create_board(&self, on_done: Callback<()>);
+
+    /// Changes of the board after `since`, the whole board if `since` is 0.
+    fn fetch_lines(&self, since: u64, on_done: Callback<Delta>);
+
+    /// Sends operations in normalized coordinates, in the order they are posted.
+    ///
+    /// Adding, removing and clearing lines are all operations. Operations that failed are not sent again.
+    fn post_operations(&self, operations: Vec<Operation>, on_done: Callback<()>);
+
+    /// Tells the backend the client is on the board, answering with everyone who is.
+    fn say_hello(&self, hello: Hello, on_done: Callback<Presence>);
+}
@@ -0,0 +1,164 @@
+use std::sync::{Arc, Mutex};
+
+use shared::{
+    display_name, participant_color, Delta, Hello, Lines, Operation, Participant, Presence,
+};
+
+use crate::requests::TransportError;
+
+use super::{BackendClient, Callback};
+
+#[derive(Default)]
+struct Board {
+    lines: Lines,
+    revision: u64,
+    operations: Vec<Operation>,
+    participants: Vec<Participant>,
+    offline: bool,
+    /// Most operations accepted in one post, like the `max_operations` of the backend.
+    max_operations: Option<usize>,
+}
+
+/// Keeps the board in memory and answers right away, for running the app without a backend.
+///
+/// Clones share the same board, so a test can keep one to look at what the app sent.
+#[derive(Clone, Default)]
+pub struct FakeClient {
+    board: Arc<Mutex<Board>>,
+}
+
+impl FakeClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies operations as if another client had posted them.
+    pub fn apply(&self, operations: Vec<Operation>) {
+        let mut board = self.board.lock().unwrap();
+
+        for operation in operations {
+            board.lines.apply(operation);
+            board.revision += 1;
+        }
+    }
+
+    /// Every operation posted to the client, in order.
+    pub fn operations(&self) -> Vec<Operation> {
+        self.board.lock().unwrap().operations.clone()
+    }
+
+    pub fn lines(&self) -> Lines {
+        self.board.lock().unwrap().lines.clone()
+    }
+
+    pub fn participants(&self) -> Vec<Participant> {
+        self.board.lock().unwrap().participants.clone()
+    }
+
+    /// Makes every request fail with a network error until it is set back.
+    pub fn set_offline(&self, offline: bool) {
+        self.board.lock().unwrap().offline = offline;
+    }
+
+    /// Refuses posts with more than `max_operations` with 413 like the backend does.
+    pub fn set_max_operations(&self, max_operations: usize) {
+        self.board.lock().unwrap().max_operations = Some(max_operations);
+    }
+
+    fn check_online(&self) -> Result<(), TransportError> {
+        match self.board.lock().unwrap().offline {
+            true => Err(TransportError::Network("offline".to_string())),
+            false => Ok(()),
+        }
+    }
+}
+
+impl BackendClient for FakeClient {
+    fn create_board(&self, on_done: Callback<()>) {
+        on_done(self.check_online());
+    }
+
+    /// Answers with the whole board unless nothing changed after `since`.
+    ///
+    /// Like the backend, the delta is only marked full for revision 0 or one the board does not
+    /// have yet.
+    fn fetch_lines(&self, since: u64, on_done: Callback<Delta>) {
+        if let Err(e) = self.check_online() {
+            return on_done(Err(e));
+        }
+
+        let delta = {
+            let board = self.board.lock().unwrap();
+
+            Delta {
+                revision: board.revision,
+                full: since == 0 || since > board.revision,
+                lines: match since == board.revision {
+                    true => Lines::default(),
+                    false => board.lines.clone(),
+                },
+            }
+        };
+
+        on_done(Ok(delta));
+    }
+
+    fn post_operations(&self, operations: Vec<Operation>, on_done: Callback<()>) {
+        if let Err(e) = self.check_online() {
+            return on_done(Err(e));
+        }
+
+        let max_operations = self.board.lock().unwrap().max_operations;
+
+        if max_operations.is_some_and(|max| operations.len() > max) {
+            return on_done(Err(TransportError::Status {
+                code: 413,
+                body: "Too many operations".to_string(),
+            }));
+        }
+
+        self.board
+            .lock()
+            .unwrap()
+            .operations
+            .extend(operations.iter().cloned());
+
+        self.apply(operations);
+
+        on_done(Ok(()));
+    }
+
+    /// Sessions are numbered from 1 in the order clients first say hello, and colored in that order.
+    fn say_hello(&self, hello: Hello, on_done: Callback<Presence>) {
+        if let Err(e) = self.check_online() {
+            return on_done(Err(e));
+        }
+
+        let presence = {
+            let mut board = self.board.lock().unwrap();
+
+            let session = hello.session.unwrap_or(board.participants.len() as u64 + 1);
+
+            board
+                .participants
+                .retain(|participant| participant.session != session);
+
+            let color = participant_color(session as usize - 1);
+
+            board.participants.push(Participant {
+                session,
+                name: display_name(&hello.name),
+                color,
+                last_seen: 0,
+            });
+
+            Presence {
+                session,
+                color,
+                participants: board.participants.clone(),
+            }
+        };
+
+        on_done(Ok(presence));
+    }
+}
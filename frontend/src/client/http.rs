@@ -0,0 +1,65 @@
+use shared::{Delta, Hello, Operation, Presence};
+
+use crate::outbox::Outbox;
+use crate::requests::{execute, send_get_request, send_post_request, TransportError};
+use crate::settings::Settings;
+
+use super::{BackendClient, Callback};
+
+/// Talks to the backend over HTTP, through `fetch` on the web and `ureq` natively.
+pub struct HttpClient {
+    settings: Settings,
+    outbox: Outbox,
+}
+
+impl HttpClient {
+    pub fn new(settings: Settings) -> Self {
+        let outbox = Outbox::new(settings.board_url("operations"));
+
+        Self { settings, outbox }
+    }
+}
+
+impl BackendClient for HttpClient {
+    fn create_board(&self, on_done: Callback<()>) {
+        let url = self.settings.board_base_url();
+
+        execute(async move {
+            let result = match send_post_request(&url, "").await {
+                Err(TransportError::Status { code: 409, .. }) => Ok(()),
+                result => result.map(|_| ()),
+            };
+
+            on_done(result);
+        });
+    }
+
+    fn fetch_lines(&self, since: u64, on_done: Callback<Delta>) {
+        let url = format!("{}?since={}", self.settings.board_url("lines"), since);
+
+        execute(async move {
+            let result = send_get_request(&url).await;
+
+            on_done(result.and_then(|delta| Ok(delta.parse::<Delta>()?)));
+        });
+    }
+
+    fn post_operations(&self, operations: Vec<Operation>, on_done: Callback<()>) {
+        self.outbox.push(operations, on_done);
+    }
+
+    fn say_hello(&self, hello: Hello, on_done: Callback<Presence>) {
+        let url = self.settings.board_url("presence");
+
+        let body = match serde_json::to_string(&hello) {
+            Ok(body) => body,
+            Err(e) => return on_done(Err(e.into())),
+        };
+
+        execute(async move {
+            let result = send_post_request(&url, &body).await;
+
+            on_done(result.and_then(|presence| Ok(serde_json::from_str(&presence)?)));
+        });
+    }
+}
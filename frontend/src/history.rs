@@ -0,0 +1,179 @@
+use shared::{Layer, LineId, Lines, Operation, Timestamp, DEFAULT_LAYER};
+
+/// Number of actions that can be undone.
+const MAX_STEPS: usize = 100;
+
+/// One action of the local user, as the operations that undo and redo it.
+#[derive(Default)]
+struct Step {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+/// Undo and redo stacks of the local user.
+///
+/// Undoing does not roll back the board, it sends new operations that revert the action.
+/// Those get new timestamps, so they win over the action and collaborators see the undo.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    /// Action that is still going on, like a drag of the eraser.
+    open: Option<Step>,
+}
+
+impl History {
+    /// Remembers `operation` before it is applied to `lines`.
+    pub fn record(&mut self, operation: &Operation, lines: &Lines) {
+        self.record_all(std::slice::from_ref(operation), lines);
+    }
+
+    /// Remembers operations that are undone together, before they are applied to `lines`.
+    pub fn record_all(&mut self, operations: &[Operation], lines: &Lines) {
+        self.finish();
+
+        let undo = inverse_all(operations, lines);
+
+        if undo.is_empty() {
+            return;
+        }
+
+        self.push(Step {
+            undo,
+            redo: operations.to_vec(),
+        });
+    }
+
+    /// Remembers operations that are undone together with the ones recorded before them, until
+    /// the action is [finished](Self::finish).
+    pub fn extend(&mut self, operations: &[Operation], lines: &Lines) {
+        let mut undo = inverse_all(operations, lines);
+
+        if undo.is_empty() {
+            return;
+        }
+
+        let step = self.open.get_or_insert_with(Step::default);
+
+        // The newest operations are undone first.
+        undo.append(&mut step.undo);
+        step.undo = undo;
+        step.redo.extend_from_slice(operations);
+    }
+
+    /// Ends the action that is going on, from now on it is undone in one step.
+    pub fn finish(&mut self) {
+        if let Some(step) = self.open.take() {
+            self.push(step);
+        }
+    }
+
+    fn push(&mut self, step: Step) {
+        self.undo.push(step);
+
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+
+        self.redo.clear();
+    }
+
+    /// Operations that undo the last action.
+    pub fn undo(&mut self) -> Option<Vec<Operation>> {
+        self.finish();
+
+        let step = self.undo.pop()?;
+        let operations = step.undo.clone();
+
+        self.redo.push(step);
+
+        Some(operations)
+    }
+
+    /// Operations that redo the last undone action.
+    pub fn redo(&mut self) -> Option<Vec<Operation>> {
+        self.finish();
+
+        let step = self.redo.pop()?;
+        let operations = step.redo.clone();
+
+        self.undo.push(step);
+
+        Some(operations)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.open.is_some()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+/// Operations that revert `operations` on `lines`, from the last to the first.
+fn inverse_all(operations: &[Operation], lines: &Lines) -> Vec<Operation> {
+    operations
+        .iter()
+        .rev()
+        .flat_map(|operation| inverse(operation, lines))
+        .collect()
+}
+
+/// Operations that revert `operation` on `lines`, their timestamps are set when they are sent.
+fn inverse(operation: &Operation, lines: &Lines) -> Vec<Operation> {
+    let restore = |ids: &mut dyn Iterator<Item = &LineId>| {
+        ids.filter_map(|id| {
+            Some(Operation::AddLine {
+                id: *id,
+                line: lines.get(id)?.clone(),
+                timestamp: Timestamp::default(),
+            })
+        })
+        .collect()
+    };
+
+    match operation {
+        // Replaces an earlier version, like edited text or a line moved to another layer, unless
+        // the line only grew while it was streamed.
+        Operation::AddLine { id, line, .. } => match lines.get(id) {
+            Some(old)
+                if old.text != line.text
+                    || old.layer != line.layer
+                    || !line.starts_with(&old.points) =>
+            {
+                restore(&mut std::iter::once(id))
+            }
+            _ => vec![Operation::RemoveLines {
+                ids: vec![*id],
+                timestamp: Timestamp::default(),
+            }],
+        },
+        // Streamed points are undone together with their line.
+        Operation::AppendPoints { .. } => Vec::new(),
+        Operation::RemoveLines { ids, .. } => restore(&mut ids.iter()),
+        Operation::Clear { .. } => restore(&mut lines.keys()),
+        // Layers cannot be removed, a new layer has nothing to go back to.
+        Operation::SetLayer { id, .. } => match lines.layer(*id) {
+            Some(old) => vec![Operation::SetLayer {
+                id: *id,
+                layer: old.clone(),
+                timestamp: Timestamp::default(),
+            }],
+            None if *id == DEFAULT_LAYER => vec![Operation::SetLayer {
+                id: *id,
+                layer: Layer::default(),
+                timestamp: Timestamp::default(),
+            }],
+            None => Vec::new(),
+        },
+        Operation::Transform { ids, transform, .. } => match transform.inverse() {
+            Some(inverse) => vec![Operation::Transform {
+                ids: ids.clone(),
+                transform: inverse,
+                timestamp: Timestamp::default(),
+            }],
+            None => restore(&mut ids.iter()),
+        },
+    }
+}
@@ -1,5 +1,15 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+mod client;
+mod history;
+mod outbox;
+mod pending;
 pub mod requests;
+mod selection;
+mod settings;
+mod status;
+mod websocket;
 pub use app::App;
+pub use client::{BackendClient, Callback, FakeClient, HttpClient};
+pub use settings::Settings;
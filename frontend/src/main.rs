@@ -1,8 +1,26 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+// When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
-fn main() {}
+fn main() -> eframe::Result {
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
+
+    let settings = frontend::Settings::from_args();
+
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([1280.0, 800.0])
+            .with_title("WebPaint"),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "WebPaint",
+        native_options,
+        Box::new(|cc| Ok(Box::new(frontend::App::new(cc, settings)))),
+    )
+}
 
 // When compiling to web using trunk:
 #[cfg(target_arch = "wasm32")]
@@ -30,7 +48,12 @@ fn main() {
             .start(
                 canvas,
                 web_options,
-                Box::new(|cc| Ok(Box::new(frontend::App::new(cc)))),
+                Box::new(|cc| {
+                    let settings =
+                        frontend::Settings::from_location(&cc.integration_info.web_info.location);
+
+                    Ok(Box::new(frontend::App::new(cc, settings)))
+                }),
             )
             .await;
 
@@ -0,0 +1,152 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use shared::{Envelope, Operation};
+
+use crate::client::Callback;
+use crate::pending::{post_size, MAX_POST_BYTES, MAX_POST_OPERATIONS};
+use crate::requests::{execute, send_post_request, TransportError};
+
+#[derive(Default)]
+struct State {
+    /// Pushed operations, each with the callback that is called once they were sent or failed.
+    queue: VecDeque<(Vec<Operation>, Callback<()>)>,
+    sending: bool,
+}
+
+impl State {
+    /// Takes the oldest pushes that fit into one post together, at least one of them.
+    ///
+    /// Stops sending once the queue is empty.
+    fn next_batch(&mut self) -> Option<(Vec<Operation>, Vec<Callback<()>>)> {
+        let mut operations = Vec::new();
+        let mut callbacks = Vec::new();
+        let mut bytes = 0;
+
+        while let Some((pushed, _)) = self.queue.front() {
+            let size: usize = pushed.iter().map(post_size).sum();
+
+            if !callbacks.is_empty()
+                && (operations.len() + pushed.len() > MAX_POST_OPERATIONS
+                    || bytes + size > MAX_POST_BYTES)
+            {
+                break;
+            }
+
+            let (pushed, on_done) = self.queue.pop_front().unwrap();
+
+            operations.extend(pushed);
+            callbacks.push(on_done);
+            bytes += size;
+        }
+
+        self.sending = !callbacks.is_empty();
+
+        self.sending.then_some((operations, callbacks))
+    }
+}
+
+/// Sends operations to the backend in the order they were pushed.
+///
+/// Streamed points of a line only make sense after the line itself, so the
+/// backend must never see them out of order. Operations pushed while a request
+/// is in flight are sent together in the next one, as long as they stay below
+/// [`MAX_POST_OPERATIONS`] and [`MAX_POST_BYTES`].
+#[derive(Clone)]
+pub struct Outbox {
+    url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl Outbox {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            state: Default::default(),
+        }
+    }
+
+    pub fn push(&self, operations: Vec<Operation>, on_done: Callback<()>) {
+        let mut state = self.state.lock().unwrap();
+
+        state.queue.push_back((operations, on_done));
+
+        if !state.sending {
+            state.sending = true;
+
+            execute(self.clone().drain());
+        }
+    }
+
+    async fn drain(self) {
+        loop {
+            let Some((operations, callbacks)) = self.state.lock().unwrap().next_batch() else {
+                break;
+            };
+
+            let result = match serde_json::to_string(&Envelope::new(operations)) {
+                Ok(body) => send_post_request(&self.url, &body).await.map(|_| ()),
+                Err(e) => Err(TransportError::from(e)),
+            };
+
+            match &result {
+                Ok(()) => log::debug!("Successfully sent operations to backend"),
+                Err(e) => log::error!("Error: {}", e),
+            }
+
+            for on_done in callbacks {
+                on_done(result.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::Timestamp;
+
+    use super::*;
+
+    fn removals(count: u64) -> Vec<Operation> {
+        (1..=count)
+            .map(|counter| Operation::RemoveLines {
+                ids: vec![Timestamp { counter, client: 1 }.id()],
+                timestamp: Timestamp { counter, client: 1 },
+            })
+            .collect()
+    }
+
+    fn batch_sizes(state: &mut State) -> Vec<(usize, usize)> {
+        std::iter::from_fn(|| state.next_batch())
+            .map(|(operations, callbacks)| (operations.len(), callbacks.len()))
+            .collect()
+    }
+
+    #[test]
+    fn queued_pushes_are_sent_together_below_the_limits() {
+        let mut state = State::default();
+
+        for count in [
+            1,
+            2,
+            MAX_POST_OPERATIONS as u64,
+            MAX_POST_OPERATIONS as u64,
+            3,
+        ] {
+            state.queue.push_back((removals(count), Box::new(|_| {})));
+        }
+
+        assert_eq!(
+            batch_sizes(&mut state),
+            [
+                (3, 2),
+                (MAX_POST_OPERATIONS, 1),
+                (MAX_POST_OPERATIONS, 1),
+                (3, 1)
+            ]
+        );
+        assert!(!state.sending);
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use shared::Operation;
+use web_time::{Duration, Instant};
+
+/// Wait before the first retry, doubled after every failure up to [`MAX_BACKOFF`].
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Most operations sent in one post, well below the 10 000 the backend accepts by default.
+pub const MAX_POST_OPERATIONS: usize = 1_000;
+
+/// Most bytes of operations sent in one post, half of the 2 MiB the backend accepts by default.
+pub const MAX_POST_BYTES: usize = 1024 * 1024;
+
+/// Operations in normalized coordinates that the backend has not confirmed yet.
+///
+/// Every post gets a number, its operations stay here until the post succeeds. Failed
+/// operations are sent again with exponential backoff, in posts small enough for the backend to
+/// accept. Sending an operation twice is harmless, so nothing is lost by retrying too much.
+/// Operations the backend refuses would be refused again, they are dropped instead.
+#[derive(Default)]
+pub struct Pending {
+    next_post: u64,
+    in_flight: BTreeMap<u64, Vec<Operation>>,
+    /// Operations waiting for the next retry, oldest first.
+    failed: Vec<Operation>,
+    failures: u32,
+    next_retry: Option<Instant>,
+}
+
+impl Pending {
+    /// Starts with operations from an earlier run, which are sent again right away.
+    pub fn restore(operations: Vec<Operation>) -> Self {
+        Self {
+            failed: operations,
+            next_retry: Some(Instant::now()),
+            ..Default::default()
+        }
+    }
+
+    /// Records a post of `operations` and returns its number.
+    pub fn start(&mut self, operations: Vec<Operation>) -> u64 {
+        let post = self.next_post;
+
+        self.next_post += 1;
+        self.in_flight.insert(post, operations);
+
+        post
+    }
+
+    pub fn succeeded(&mut self, post: u64) {
+        self.in_flight.remove(&post);
+        self.failures = 0;
+    }
+
+    pub fn failed(&mut self, post: u64) {
+        let Some(operations) = self.in_flight.remove(&post) else {
+            return;
+        };
+
+        self.failed.extend(operations);
+
+        let backoff = MIN_BACKOFF
+            .saturating_mul(1 << self.failures.min(16))
+            .min(MAX_BACKOFF);
+
+        self.failures += 1;
+        self.next_retry = Some(Instant::now() + backoff);
+
+        log::info!(
+            "{} operations not sent, retrying in {:?}",
+            self.failed.len(),
+            backoff
+        );
+    }
+
+    /// Forgets a post the backend refused, returns how many operations were dropped with it.
+    pub fn rejected(&mut self, post: u64) -> usize {
+        self.in_flight
+            .remove(&post)
+            .map_or(0, |operations| operations.len())
+    }
+
+    /// Takes the failed operations once their retry is due, split into posts.
+    pub fn due(&mut self) -> Option<Vec<Vec<Operation>>> {
+        let due = self.next_retry.is_some_and(|at| Instant::now() >= at);
+
+        if !due || self.failed.is_empty() {
+            return None;
+        }
+
+        self.next_retry = None;
+
+        Some(chunks(std::mem::take(&mut self.failed)))
+    }
+
+    /// Makes the failed operations due now instead of after the backoff.
+    pub fn retry_now(&mut self) {
+        if !self.failed.is_empty() {
+            self.next_retry = Some(Instant::now());
+        }
+    }
+
+    /// Time until the next retry, if one is waiting.
+    pub fn next_retry_in(&self) -> Option<Duration> {
+        self.next_retry
+            .filter(|_| !self.failed.is_empty())
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn has_failed(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// Number of operations not confirmed yet.
+    pub fn len(&self) -> usize {
+        self.in_flight.values().map(Vec::len).sum::<usize>() + self.failed.len()
+    }
+
+    /// Everything not confirmed yet, to be stored across restarts.
+    pub fn operations(&self) -> Vec<Operation> {
+        self.failed
+            .iter()
+            .chain(self.in_flight.values().flatten())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Bytes `operation` takes up in a post.
+pub fn post_size(operation: &Operation) -> usize {
+    serde_json::to_string(operation).map_or(0, |json| json.len())
+}
+
+/// Splits `operations` into posts below [`MAX_POST_OPERATIONS`] and [`MAX_POST_BYTES`].
+///
+/// An operation larger than the byte limit is posted on its own.
+fn chunks(operations: Vec<Operation>) -> Vec<Vec<Operation>> {
+    let mut chunks: Vec<Vec<Operation>> = Vec::new();
+    let mut bytes = 0;
+
+    for operation in operations {
+        let size = post_size(&operation);
+
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() < MAX_POST_OPERATIONS && bytes + size <= MAX_POST_BYTES => {
+                chunk.push(operation);
+                bytes += size;
+            }
+            _ => {
+                chunks.push(vec![operation]);
+                bytes = size;
+            }
+        }
+    }
+
+    chunks
+}
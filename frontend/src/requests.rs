@@ -1,96 +1,59 @@
-use std::future::Future;
-
-use wasm_bindgen_futures::wasm_bindgen;
-
-use web_sys::wasm_bindgen::JsValue;
-
-pub fn execute<F: Future<Output = ()> + 'static>(f: F) {
-    wasm_bindgen_futures::spawn_local(f);
+//! Requests to the backend, through `fetch` on the web and a blocking HTTP client natively.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::{execute, send_get_request, send_post_request};
+#[cfg(target_arch = "wasm32")]
+pub use web::{execute, send_get_request, send_post_request};
+
+/// Why a request to the backend failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransportError {
+    /// The backend could not be reached or the connection broke.
+    Network(String),
+    /// The backend answered with an error status.
+    Status { code: u16, body: String },
+    /// The answer of the backend could not be read.
+    Decode(String),
 }
 
-#[wasm_bindgen::prelude::wasm_bindgen]
-pub async fn send_post_request(url: &str, body: &str) -> Result<String, JsValue> {
-    use wasm_bindgen::prelude::*;
-    use wasm_bindgen_futures::JsFuture;
-    use web_sys::wasm_bindgen::JsValue;
-    use web_sys::{Request, RequestInit, Response};
-
-    let body = body.to_string();
-
-    log::debug!("Sending POST request to: {}", url);
-    log::trace!("Body: {}", body);
-
-    let opts = RequestInit::new();
-    opts.set_method("POST");
-    opts.set_mode(web_sys::RequestMode::Cors);
-
-    opts.set_body(&JsValue::from_str(&body));
-
-    let request = Request::new_with_str_and_init(url, &opts)?;
-
-    request.headers().set("Accept", "application/json")?;
-    request.headers().set("Content-Type", "application/json")?;
-
-    let window = web_sys::window().unwrap();
-
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
-
-    assert!(resp_value.is_instance_of::<web_sys::Response>());
-
-    let resp: Response = resp_value.dyn_into().unwrap();
-
-    let content: String = JsFuture::from(resp.text()?).await?.as_string().unwrap();
-
-    if !resp.ok() {
-        return Err(JsValue::from_str(&format!(
-            "Request failed with status: {} and error: {}",
-            resp.status(),
-            content
-        )));
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "Cannot reach the backend: {}", e),
+            Self::Status { code, body } if body.is_empty() => {
+                write!(f, "The backend answered with status {}", code)
+            }
+            Self::Status { code, body } => {
+                write!(f, "The backend answered with status {}: {}", code, body)
+            }
+            Self::Decode(e) => write!(f, "Unexpected answer from the backend: {}", e),
+        }
     }
-
-    log::debug!("Response Content: {}", content);
-
-    Ok(content)
 }
 
-#[wasm_bindgen::prelude::wasm_bindgen]
-pub async fn send_get_request(url: &str) -> Result<String, JsValue> {
-    use wasm_bindgen::prelude::*;
-    use wasm_bindgen_futures::JsFuture;
-    use web_sys::Request;
-    use web_sys::RequestInit;
-    use web_sys::RequestMode;
-    use web_sys::Response;
-
-    log::debug!("Sending GET request to: {}", url);
-
-    let opts = RequestInit::new();
-    opts.set_method("GET");
-    opts.set_mode(RequestMode::Cors);
-
-    let request = Request::new_with_str_and_init(url, &opts)?;
-
-    request.headers().set("Accept", "application/json")?;
-
-    let window = web_sys::window().unwrap();
-
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
-
-    assert!(resp_value.is_instance_of::<web_sys::Response>());
-
-    let resp: Response = resp_value.dyn_into().unwrap();
-
-    if !resp.ok() {
-        return Err(JsValue::from_str(&format!(
-            "Request failed with status: {}",
-            resp.status()
-        )));
+impl TransportError {
+    /// Whether sending the same request again cannot succeed, because the backend refused it.
+    ///
+    /// Timeouts and rate limits are worth retrying like network errors.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Self::Status { code, .. } => (400..500).contains(code) && *code != 408 && *code != 429,
+            Self::Network(_) | Self::Decode(_) => false,
+        }
     }
+}
 
-    let content: String = JsFuture::from(resp.text()?).await?.as_string().unwrap();
-
-    log::debug!("Response Content: {}", content);
+impl std::error::Error for TransportError {}
 
-    Ok(content)
+impl From<serde_json::Error> for TransportError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Decode(e.to_string())
+    }
 }
@@ -0,0 +1,47 @@
+use std::future::Future;
+
+use super::TransportError;
+
+/// Runs `f` on its own thread, where requests block until they are done.
+pub fn execute<F: Future<Output = ()> + Send + 'static>(f: F) {
+    std::thread::spawn(move || pollster::block_on(f));
+}
+
+pub async fn send_post_request(url: &str, body: &str) -> Result<String, TransportError> {
+    log::debug!("Sending POST request to: {}", url);
+    log::trace!("Body: {}", body);
+
+    let response = ureq::post(url)
+        .set("Accept", "application/json")
+        .set("Content-Type", "application/json")
+        .send_string(body);
+
+    read_response(response)
+}
+
+pub async fn send_get_request(url: &str) -> Result<String, TransportError> {
+    log::debug!("Sending GET request to: {}", url);
+
+    let response = ureq::get(url).set("Accept", "application/json").call();
+
+    read_response(response)
+}
+
+fn read_response(response: Result<ureq::Response, ureq::Error>) -> Result<String, TransportError> {
+    match response {
+        Ok(response) => {
+            let content = response
+                .into_string()
+                .map_err(|e| TransportError::Network(e.to_string()))?;
+
+            log::debug!("Response Content: {}", content);
+
+            Ok(content)
+        }
+        Err(ureq::Error::Status(code, response)) => Err(TransportError::Status {
+            code,
+            body: response.into_string().unwrap_or_default(),
+        }),
+        Err(e) => Err(TransportError::Network(e.to_string())),
+    }
+}
@@ -0,0 +1,84 @@
+use std::future::Future;
+
+use wasm_bindgen_futures::JsFuture;
+use web_sys::wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Request, RequestInit, RequestMode, Response};
+
+use super::TransportError;
+
+pub fn execute<F: Future<Output = ()> + 'static>(f: F) {
+    wasm_bindgen_futures::spawn_local(f);
+}
+
+pub async fn send_post_request(url: &str, body: &str) -> Result<String, TransportError> {
+    log::debug!("Sending POST request to: {}", url);
+    log::trace!("Body: {}", body);
+
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+
+    opts.set_body(&JsValue::from_str(body));
+
+    let request = Request::new_with_str_and_init(url, &opts).map_err(network_error)?;
+
+    request
+        .headers()
+        .set("Accept", "application/json")
+        .map_err(network_error)?;
+    request
+        .headers()
+        .set("Content-Type", "application/json")
+        .map_err(network_error)?;
+
+    fetch(&request).await
+}
+
+pub async fn send_get_request(url: &str) -> Result<String, TransportError> {
+    log::debug!("Sending GET request to: {}", url);
+
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let request = Request::new_with_str_and_init(url, &opts).map_err(network_error)?;
+
+    request
+        .headers()
+        .set("Accept", "application/json")
+        .map_err(network_error)?;
+
+    fetch(&request).await
+}
+
+async fn fetch(request: &Request) -> Result<String, TransportError> {
+    let window =
+        web_sys::window().ok_or_else(|| TransportError::Network("no window".to_string()))?;
+
+    let resp: Response = JsFuture::from(window.fetch_with_request(request))
+        .await
+        .map_err(network_error)?
+        .dyn_into()
+        .map_err(|_| TransportError::Decode("fetch did not return a response".to_string()))?;
+
+    let content = JsFuture::from(resp.text().map_err(network_error)?)
+        .await
+        .map_err(network_error)?
+        .as_string()
+        .ok_or_else(|| TransportError::Decode("the response is not text".to_string()))?;
+
+    if !resp.ok() {
+        return Err(TransportError::Status {
+            code: resp.status(),
+            body: content,
+        });
+    }
+
+    log::debug!("Response Content: {}", content);
+
+    Ok(content)
+}
+
+fn network_error(e: JsValue) -> TransportError {
+    TransportError::Network(e.as_string().unwrap_or_else(|| format!("{:?}", e)))
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeSet;
+
+use egui::{vec2, Modifiers, Pos2, Rect, Vec2};
+use shared::{polygon_contains, LineId, Lines, Transform};
+
+/// Smallest distance of a handle to the one opposite of it, so scaling never divides by zero.
+const MIN_EXTENT: f32 = 1e-3;
+
+/// What dragging does to the selected lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Handle {
+    Move,
+    /// Scales away from `anchor`, the corner opposite of the dragged one.
+    Scale {
+        anchor: Pos2,
+    },
+    Rotate {
+        center: Pos2,
+    },
+}
+
+enum Gesture {
+    /// Selects the lines within a rectangle, or within the drawn outline if `freeform`.
+    Lasso { points: Vec<Pos2>, freeform: bool },
+    Transform {
+        handle: Handle,
+        start: Pos2,
+        current: Pos2,
+    },
+}
+
+/// Lines picked with the selection tool and the drag that is going on, all in canvas coordinates.
+///
+/// Pressing on a line picks it, pressing on empty space starts a rubber band or, with Alt, a lasso.
+/// Shift adds to the selection. Dragging the selection or its handles moves, scales or rotates it,
+/// the lines only change once the drag ends.
+#[derive(Default)]
+pub struct Selection {
+    pub ids: BTreeSet<LineId>,
+    gesture: Option<Gesture>,
+}
+
+impl Selection {
+    /// Starts a drag at `pos`, `radius` is how close the pointer has to be to a line or handle.
+    pub fn press(&mut self, lines: &Lines, pos: Pos2, radius: f32, modifiers: Modifiers) {
+        let bounds = self.bounds(lines);
+
+        if let Some(handle) = bounds.and_then(|bounds| handle_at(bounds, pos, radius)) {
+            self.gesture = Some(Gesture::Transform {
+                handle,
+                start: pos,
+                current: pos,
+            });
+
+            return;
+        }
+
+        match line_at(lines, pos, radius) {
+            Some(id) if modifiers.shift && self.ids.contains(&id) => {
+                self.ids.remove(&id);
+
+                // Dragging on from here adds lines with a rubber band like on empty space.
+                self.gesture = Some(Gesture::Lasso {
+                    points: vec![pos],
+                    freeform: modifiers.alt,
+                });
+            }
+            Some(id) => {
+                if !modifiers.shift && !self.ids.contains(&id) {
+                    self.ids.clear();
+                }
+
+                self.ids.insert(id);
+
+                self.gesture = Some(Gesture::Transform {
+                    handle: Handle::Move,
+                    start: pos,
+                    current: pos,
+                });
+            }
+            None if bounds.is_some_and(|bounds| bounds.expand(radius).contains(pos)) => {
+                self.gesture = Some(Gesture::Transform {
+                    handle: Handle::Move,
+                    start: pos,
+                    current: pos,
+                });
+            }
+            None => {
+                if !modifiers.shift {
+                    self.ids.clear();
+                }
+
+                self.gesture = Some(Gesture::Lasso {
+                    points: vec![pos],
+                    freeform: modifiers.alt,
+                });
+            }
+        }
+    }
+
+    pub fn drag(&mut self, pos: Pos2) {
+        match &mut self.gesture {
+            Some(Gesture::Lasso { points, .. }) => {
+                if points.last() != Some(&pos) {
+                    points.push(pos);
+                }
+            }
+            Some(Gesture::Transform { current, .. }) => *current = pos,
+            None => {}
+        }
+    }
+
+    /// Ends the drag, returns the transform to apply to the selected lines if they were changed.
+    pub fn release(&mut self, lines: &Lines) -> Option<Transform> {
+        match self.gesture.take()? {
+            Gesture::Lasso { points, freeform } => {
+                let outline = outline(points, freeform);
+
+                let inside = lines
+                    .iter()
+                    .filter(|(id, line)| {
+                        lines.is_editable(id)
+                            && !line.is_empty()
+                            && line
+                                .paths()
+                                .iter()
+                                .flatten()
+                                .all(|pos| polygon_contains(&outline, *pos))
+                    })
+                    .map(|(id, _)| *id);
+
+                self.ids.extend(inside);
+
+                None
+            }
+            Gesture::Transform {
+                handle,
+                start,
+                current,
+            } => {
+                if start == current {
+                    return None;
+                }
+
+                Some(handle_transform(handle, start, current))
+            }
+        }
+    }
+
+    /// Forgets the selected lines that are gone or locked, like the ones a collaborator removed.
+    pub fn retain(&mut self, lines: &Lines) {
+        self.ids.retain(|id| lines.is_editable(id));
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+        self.gesture = None;
+    }
+
+    /// What the current drag does to the selected lines, to draw them where they will end up.
+    pub fn transform(&self) -> Transform {
+        match &self.gesture {
+            Some(Gesture::Transform {
+                handle,
+                start,
+                current,
+            }) => handle_transform(*handle, *start, *current),
+            _ => Transform::IDENTITY,
+        }
+    }
+
+    /// Outline of the rubber band or lasso being drawn.
+    pub fn lasso(&self) -> Option<Vec<Pos2>> {
+        match &self.gesture {
+            Some(Gesture::Lasso { points, freeform }) => Some(outline(points.clone(), *freeform)),
+            _ => None,
+        }
+    }
+
+    /// Box around the selected lines as they are now, `None` if nothing is selected.
+    pub fn bounds(&self, lines: &Lines) -> Option<Rect> {
+        let rect = self
+            .ids
+            .iter()
+            .filter_map(|id| lines.get(id))
+            .flat_map(|line| line.paths())
+            .flatten()
+            .fold(Rect::NOTHING, |rect, pos| rect.union(Rect::from_pos(pos)));
+
+        // Expanded so a single dot or a straight line still gets a box that can be grabbed.
+        (rect.min.x <= rect.max.x).then(|| rect.expand(MIN_EXTENT))
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.gesture.is_some()
+    }
+}
+
+/// Where the handles of a selection with `bounds` are, `radius` keeps the rotate handle clear of the box.
+pub fn handles(bounds: Rect, radius: f32) -> Vec<(Handle, Pos2)> {
+    let corners = [
+        (bounds.left_top(), bounds.right_bottom()),
+        (bounds.right_top(), bounds.left_bottom()),
+        (bounds.right_bottom(), bounds.left_top()),
+        (bounds.left_bottom(), bounds.right_top()),
+    ];
+
+    corners
+        .into_iter()
+        .map(|(corner, anchor)| (Handle::Scale { anchor }, corner))
+        .chain(std::iter::once((
+            Handle::Rotate {
+                center: bounds.center(),
+            },
+            bounds.center_top() - vec2(0.0, 4.0 * radius),
+        )))
+        .collect()
+}
+
+/// The closed outline of a lasso, or of the rectangle spanned by the first and last point of a rubber band.
+fn outline(points: Vec<Pos2>, freeform: bool) -> Vec<Pos2> {
+    if freeform {
+        return points;
+    }
+
+    let rect = Rect::from_two_pos(points[0], *points.last().unwrap());
+
+    vec![
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+        rect.left_top(),
+    ]
+}
+
+fn handle_at(bounds: Rect, pos: Pos2, radius: f32) -> Option<Handle> {
+    handles(bounds, radius)
+        .into_iter()
+        .find(|(_, handle_pos)| handle_pos.distance(pos) <= radius)
+        .map(|(handle, _)| handle)
+}
+
+fn handle_transform(handle: Handle, start: Pos2, current: Pos2) -> Transform {
+    match handle {
+        Handle::Move => Transform::translate(current - start),
+        Handle::Scale { anchor } => {
+            let factor = |from: f32, to: f32| match from.abs() < MIN_EXTENT {
+                true => 1.0,
+                false => to / from,
+            };
+
+            let from = start - anchor;
+            let to = current - anchor;
+
+            Transform::scale_about(
+                anchor,
+                Vec2::new(factor(from.x, to.x), factor(from.y, to.y)),
+            )
+        }
+        Handle::Rotate { center } => {
+            let angle = (current - center).angle() - (start - center).angle();
+
+            Transform::rotate_about(center, angle)
+        }
+    }
+}
+
+/// The topmost line within `radius` of `pos`, lines on hidden or locked layers are skipped.
+pub fn line_at(lines: &Lines, pos: Pos2, radius: f32) -> Option<LineId> {
+    lines
+        .drawing_order()
+        .into_iter()
+        .rev()
+        .filter(|(id, _)| lines.is_editable(id))
+        .find(|(_, line)| line.distance(pos) <= radius + line.stroke.width / 2.0)
+        .map(|(id, _)| id)
+}
@@ -0,0 +1,117 @@
+use shared::{is_valid_board_id, DEFAULT_BOARD};
+
+/// Backend used by the desktop app when none is configured.
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_BACKEND_URL: &str = "http://localhost:8432";
+
+/// Where the backend runs and which board to open.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Base url of the HTTP routes, like `http://localhost:8432`.
+    pub backend_url: String,
+    /// Base url of the websocket routes, like `ws://localhost:8432`.
+    pub events_url: String,
+    pub board_id: String,
+    /// Name shown to the others on the board, a random one is picked if missing.
+    pub name: Option<String>,
+}
+
+impl Settings {
+    /// Talks to the backend at `backend_url` directly, for HTTP and websockets alike.
+    pub fn new(backend_url: &str, board_id: &str) -> Self {
+        let backend_url = backend_url.trim_end_matches('/');
+
+        Self {
+            backend_url: backend_url.to_string(),
+            events_url: backend_url.replacen("http", "ws", 1),
+            board_id: valid_board_id(Some(board_id)),
+            name: None,
+        }
+    }
+
+    /// Talks to the backend through the proxy of the server the page came from, see `Trunk.toml`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_location(location: &eframe::Location) -> Self {
+        Self {
+            backend_url: format!("{}/backend", location.origin),
+            events_url: format!("{}/backend-ws", location.origin.replacen("http", "ws", 1)),
+            board_id: board_id_from_location(location),
+            name: location
+                .query_map
+                .get("name")
+                .and_then(|values| values.first())
+                .cloned(),
+        }
+    }
+
+    /// Reads `--backend <url>`, `--board <id>` and `--name <name>`, falling back to
+    /// `WEBPAINT_BACKEND_URL`, `WEBPAINT_BOARD` and `WEBPAINT_NAME`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_args() -> Self {
+        let mut backend_url = std::env::var("WEBPAINT_BACKEND_URL").ok();
+        let mut board_id = std::env::var("WEBPAINT_BOARD").ok();
+        let mut name = std::env::var("WEBPAINT_NAME").ok();
+
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--backend" => backend_url = args.next(),
+                "--board" => board_id = args.next(),
+                "--name" => name = args.next(),
+                _ => log::warn!("Ignoring unknown argument {:?}", arg),
+            }
+        }
+
+        Self {
+            name,
+            ..Self::new(
+                backend_url.as_deref().unwrap_or(DEFAULT_BACKEND_URL),
+                board_id.as_deref().unwrap_or(DEFAULT_BOARD),
+            )
+        }
+    }
+
+    /// Url of a route of the current board.
+    /// Url of the board itself, where it is created.
+    pub fn board_base_url(&self) -> String {
+        format!("{}/boards/{}", self.backend_url, self.board_id)
+    }
+
+    pub fn board_url(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.board_base_url(), endpoint)
+    }
+
+    pub fn board_events_url(&self) -> String {
+        format!("{}/boards/{}/events", self.events_url, self.board_id)
+    }
+}
+
+/// Reads the board from `?board=<id>`, `#board=<id>` or `#<id>`.
+///
+/// `#dev` is reserved by `index.html` to skip the service worker and is not treated as a board.
+#[cfg(target_arch = "wasm32")]
+fn board_id_from_location(location: &eframe::Location) -> String {
+    let from_query = location
+        .query_map
+        .get("board")
+        .and_then(|values| values.first())
+        .map(String::as_str);
+
+    let hash = location.hash.trim_start_matches('#');
+    let from_hash = Some(hash.strip_prefix("board=").unwrap_or(hash))
+        .filter(|id| !id.is_empty() && *id != "dev");
+
+    valid_board_id(from_query.or(from_hash))
+}
+
+fn valid_board_id(id: Option<&str>) -> String {
+    match id {
+        Some(id) if is_valid_board_id(id) => id.to_string(),
+        Some(id) => {
+            log::warn!("Invalid board id {:?}, using {:?}", id, DEFAULT_BOARD);
+            DEFAULT_BOARD.to_string()
+        }
+        None => DEFAULT_BOARD.to_string(),
+    }
+}
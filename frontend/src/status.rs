@@ -0,0 +1,90 @@
+use egui::Color32;
+use web_time::{Duration, Instant};
+
+/// Failed requests in a row after which the backend counts as offline.
+const OFFLINE_AFTER: u32 = 3;
+
+/// Weight of the newest round trip in the smoothed latency.
+const RTT_SMOOTHING: f32 = 0.2;
+
+/// How well the app is talking to the backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connection {
+    /// Requests succeed and changes arrive over the websocket.
+    Connected,
+    /// Requests succeed but changes are polled until the websocket is back, or a few requests failed.
+    Reconnecting,
+    Offline,
+}
+
+impl Connection {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Connected => "Connected",
+            Self::Reconnecting => "Reconnecting",
+            Self::Offline => "Offline",
+        }
+    }
+
+    pub fn color(self) -> Color32 {
+        match self {
+            Self::Connected => Color32::from_rgb(0x3c, 0xb4, 0x4b),
+            Self::Reconnecting => Color32::from_rgb(0xf5, 0x82, 0x31),
+            Self::Offline => Color32::from_rgb(0xe6, 0x19, 0x4b),
+        }
+    }
+}
+
+/// Health of the sync with the backend, fed with the outcome of every request.
+#[derive(Default)]
+pub struct SyncStatus {
+    last_sync: Option<Instant>,
+    rtt: Option<Duration>,
+    failures: u32,
+}
+
+impl SyncStatus {
+    /// Records a request that succeeded, with its round trip if it was timed.
+    pub fn succeeded(&mut self, rtt: Option<Duration>) {
+        self.synced();
+        self.failures = 0;
+
+        if let Some(rtt) = rtt {
+            self.rtt = Some(match self.rtt {
+                Some(smoothed) => {
+                    smoothed.mul_f32(1.0 - RTT_SMOOTHING) + rtt.mul_f32(RTT_SMOOTHING)
+                }
+                None => rtt,
+            });
+        }
+    }
+
+    pub fn failed(&mut self) {
+        self.failures += 1;
+    }
+
+    /// Records changes that arrived from the backend without a request, like over the websocket.
+    pub fn synced(&mut self) {
+        self.last_sync = Some(Instant::now());
+    }
+
+    pub fn connection(&self, socket_open: bool) -> Connection {
+        if self.failures >= OFFLINE_AFTER {
+            Connection::Offline
+        } else if self.failures > 0 || !socket_open {
+            Connection::Reconnecting
+        } else {
+            Connection::Connected
+        }
+    }
+
+    /// Time since changes last arrived from the backend or it confirmed a request.
+    pub fn since_last_sync(&self) -> Option<Duration> {
+        self.last_sync.map(|at| at.elapsed())
+    }
+
+    /// Smoothed round trip of timed requests.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+}
@@ -0,0 +1,17 @@
+//! Websocket that receives the changes of a board and sends our cursor, from the browser on the web and a thread natively.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::EventSocket;
+#[cfg(target_arch = "wasm32")]
+pub use web::EventSocket;
+
+pub enum SocketEvent {
+    Opened,
+    Message(String),
+    Closed,
+}
@@ -0,0 +1,135 @@
+use std::{
+    io::ErrorKind,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use super::SocketEvent;
+
+/// How long a read blocks before the thread checks whether the socket was dropped.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Websocket that receives the changes of a board as they happen.
+///
+/// The socket runs on its own thread. Events are queued until they are picked up with
+/// [`EventSocket::try_recv`] and every event requests a repaint so they are handled without delay.
+pub struct EventSocket {
+    receiver: Receiver<SocketEvent>,
+    outgoing: Sender<String>,
+    dropped: Arc<AtomicBool>,
+}
+
+impl EventSocket {
+    pub fn connect(url: &str, ctx: &egui::Context) -> Result<Self, String> {
+        log::debug!("Connecting websocket to: {}", url);
+
+        let (sender, receiver) = channel();
+        let (outgoing, outgoing_receiver) = channel();
+
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        {
+            let url = url.to_string();
+            let ctx = ctx.clone();
+            let dropped = dropped.clone();
+
+            std::thread::Builder::new()
+                .name("websocket".to_string())
+                .spawn(move || {
+                    run(&url, &sender, &outgoing_receiver, &dropped, &ctx);
+
+                    sender.send(SocketEvent::Closed).ok();
+                    ctx.request_repaint();
+                })
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(Self {
+            receiver,
+            outgoing,
+            dropped,
+        })
+    }
+
+    pub fn try_recv(&self) -> Option<SocketEvent> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Queues `text` to be sent by the thread, it is dropped if the socket is not open.
+    pub fn send(&self, text: &str) {
+        self.outgoing.send(text.to_string()).ok();
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::Relaxed);
+    }
+}
+
+fn run(
+    url: &str,
+    sender: &Sender<SocketEvent>,
+    outgoing: &Receiver<String>,
+    dropped: &AtomicBool,
+    ctx: &egui::Context,
+) {
+    let mut socket = match tungstenite::connect(url) {
+        Ok((socket, _)) => socket,
+        Err(e) => {
+            log::error!("Error: {:?}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = set_read_timeout(&socket) {
+        log::error!("Error: {:?}", e);
+        return;
+    }
+
+    sender.send(SocketEvent::Opened).ok();
+    ctx.request_repaint();
+
+    while !dropped.load(Ordering::Relaxed) {
+        // Only what was queued since the last read, so a slow backend cannot keep us from reading.
+        for text in outgoing.try_iter().collect::<Vec<_>>() {
+            if let Err(e) = socket.send(Message::Text(text)) {
+                log::debug!("Websocket closed: {}", e);
+                return;
+            }
+        }
+
+        // Pings from the backend are answered while reading.
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                sender.send(SocketEvent::Message(text)).ok();
+                ctx.request_repaint();
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => {
+                log::debug!("Websocket closed: {}", e);
+                return;
+            }
+        }
+    }
+
+    socket.close(None).ok();
+    socket.flush().ok();
+}
+
+fn set_read_timeout(socket: &WebSocket<MaybeTlsStream<TcpStream>>) -> std::io::Result<()> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(READ_TIMEOUT)),
+        MaybeTlsStream::Rustls(stream) => stream.get_ref().set_read_timeout(Some(READ_TIMEOUT)),
+        _ => Ok(()),
+    }
+}
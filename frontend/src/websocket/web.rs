@@ -0,0 +1,90 @@
+use std::sync::mpsc::{channel, Receiver};
+
+use eframe::wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{MessageEvent, WebSocket};
+
+use super::SocketEvent;
+
+/// Websocket that receives the changes of a board as they happen.
+///
+/// Events are queued until they are picked up with [`EventSocket::try_recv`]
+/// and every event requests a repaint so they are handled without delay.
+pub struct EventSocket {
+    socket: WebSocket,
+    receiver: Receiver<SocketEvent>,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut()>,
+}
+
+impl EventSocket {
+    pub fn connect(url: &str, ctx: &egui::Context) -> Result<Self, JsValue> {
+        log::debug!("Connecting websocket to: {}", url);
+
+        let socket = WebSocket::new(url)?;
+
+        let (sender, receiver) = channel();
+
+        let on_open = {
+            let sender = sender.clone();
+            let ctx = ctx.clone();
+
+            Closure::<dyn FnMut()>::new(move || {
+                sender.send(SocketEvent::Opened).ok();
+                ctx.request_repaint();
+            })
+        };
+
+        let on_message = {
+            let sender = sender.clone();
+            let ctx = ctx.clone();
+
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                if let Some(text) = event.data().as_string() {
+                    sender.send(SocketEvent::Message(text)).ok();
+                    ctx.request_repaint();
+                }
+            })
+        };
+
+        // `onerror` is always followed by `onclose`, so that is the only one needed.
+        let on_close = {
+            let ctx = ctx.clone();
+
+            Closure::<dyn FnMut()>::new(move || {
+                sender.send(SocketEvent::Closed).ok();
+                ctx.request_repaint();
+            })
+        };
+
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            socket,
+            receiver,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        })
+    }
+
+    pub fn try_recv(&self) -> Option<SocketEvent> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Sends `text` if the socket is open, it is dropped otherwise.
+    pub fn send(&self, text: &str) {
+        self.socket.send_with_str(text).ok();
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        self.socket.close().ok();
+    }
+}
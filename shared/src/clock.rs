@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::LineId;
+
+/// Lamport timestamp of a change.
+///
+/// Ordered by counter first and client second, so of two concurrent changes the same one wins everywhere.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Timestamp {
+    pub counter: u64,
+    pub client: u64,
+}
+
+impl Timestamp {
+    /// Id of a line created at this timestamp.
+    pub fn id(self) -> LineId {
+        LineId::from(self)
+    }
+}
+
+/// Lamport clock of a single client.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    client: u64,
+    counter: u64,
+}
+
+impl Clock {
+    pub fn new(client: u64) -> Self {
+        Self { client, counter: 0 }
+    }
+
+    /// Timestamp for a new change, newer than everything this clock has seen.
+    pub fn tick(&mut self) -> Timestamp {
+        self.counter += 1;
+
+        Timestamp {
+            counter: self.counter,
+            client: self.client,
+        }
+    }
+
+    /// Newest timestamp this clock has handed out or seen, to carry it over to a later session.
+    pub fn latest(&self) -> Timestamp {
+        Timestamp {
+            counter: self.counter,
+            client: self.client,
+        }
+    }
+
+    /// Moves the clock past a timestamp received from someone else.
+    pub fn observe(&mut self, timestamp: Timestamp) {
+        self.counter = self.counter.max(timestamp.counter);
+    }
+}
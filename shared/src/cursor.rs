@@ -0,0 +1,35 @@
+use egui::Pos2;
+use serde::{Deserialize, Serialize};
+
+use crate::{points_from_canvas, points_to_canvas, Envelope};
+
+/// Where a participant is pointing, in normalized coordinates like lines.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    pub session: u64,
+    /// `None` once the pointer left the canvas.
+    pub pos: Option<Pos2>,
+}
+
+impl Cursor {
+    pub fn from_canvas(&mut self, canvas_rect: &egui::Rect) {
+        points_from_canvas(self.pos.as_mut_slice(), canvas_rect);
+    }
+
+    pub fn to_canvas(&mut self, canvas_rect: &egui::Rect) {
+        points_to_canvas(self.pos.as_mut_slice(), canvas_rect);
+    }
+}
+
+/// A message on the websocket of a board.
+///
+/// Operations are sent as a bare [`Envelope`] like before cursors existed, which is why this is untagged.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Event {
+    /// Sent by clients as well, the backend passes it on to everyone on the board.
+    Cursor {
+        cursor: Cursor,
+    },
+    Operations(Envelope),
+}
@@ -0,0 +1,150 @@
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use egui::{Color32, Vec2};
+
+use crate::{Line, Lines, ROW_HEIGHT};
+
+/// Size of exports without a background, which has no size of its own to follow.
+pub const EXPORT_SIZE_WITHOUT_BACKGROUND: Vec2 = Vec2::new(1024.0, 1024.0);
+
+/// Size in pixels of an export.
+///
+/// Normalized coordinates are fractions of the background, so the background decides the size.
+pub fn export_size(background_png: Option<&[u8]>) -> Vec2 {
+    background_png
+        .and_then(png_size)
+        .unwrap_or(EXPORT_SIZE_WITHOUT_BACKGROUND)
+}
+
+/// Width and height from the header of a PNG.
+pub fn png_size(png: &[u8]) -> Option<Vec2> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    if !png.starts_with(SIGNATURE) || png.get(12..16)? != b"IHDR" {
+        return None;
+    }
+
+    let width = u32::from_be_bytes(png.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(png.get(20..24)?.try_into().ok()?);
+
+    Some(Vec2::new(width as f32, height as f32))
+}
+
+/// Renders `lines`, which are in normalized coordinates, as an SVG document on top of the background.
+///
+/// Lines are drawn in [`Lines::drawing_order`], hidden layers are left out.
+pub fn export_svg(lines: &Lines, background_png: Option<&[u8]>) -> String {
+    let size = export_size(background_png);
+
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = size.x,
+        h = size.y
+    )
+    .unwrap();
+
+    if let Some(png) = background_png {
+        writeln!(
+            svg,
+            r#"<image width="{}" height="{}" preserveAspectRatio="none" href="data:image/png;base64,{}"/>"#,
+            size.x,
+            size.y,
+            STANDARD.encode(png)
+        )
+        .unwrap();
+    }
+
+    // Same order and filter as the canvas draws them.
+    for (_, line) in lines
+        .drawing_order()
+        .into_iter()
+        .filter(|(_, line)| line.len() >= 2)
+    {
+        if line.text_layout().is_some() {
+            write_text(&mut svg, line, size);
+            continue;
+        }
+
+        for path in line.paths() {
+            let points = path
+                .iter()
+                .map(|pos| format!("{},{}", pos.x * size.x, pos.y * size.y))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                points,
+                hex_color(line.stroke.color),
+                line.stroke.color.a() as f32 / 255.0,
+                line.stroke.width
+            )
+            .unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// Writes text as rows of `<text>`, scaled to `size` first so the font size follows the stretched image.
+fn write_text(svg: &mut String, line: &Line, size: Vec2) {
+    let mut scaled = line.clone();
+
+    for pos in scaled.iter_mut() {
+        pos.x *= size.x;
+        pos.y *= size.y;
+    }
+
+    let Some(layout) = scaled.text_layout() else {
+        return;
+    };
+
+    let rotate = match layout.angle {
+        0.0 => String::new(),
+        angle => format!(" rotate({})", angle.to_degrees()),
+    };
+
+    writeln!(
+        svg,
+        r#"<text transform="translate({},{}){}" font-size="{}" font-family="Ubuntu, sans-serif" fill="{}" fill-opacity="{}" dominant-baseline="text-before-edge" xml:space="preserve">"#,
+        layout.pos.x,
+        layout.pos.y,
+        rotate,
+        layout.font_size,
+        hex_color(line.stroke.color),
+        line.stroke.color.a() as f32 / 255.0
+    )
+    .unwrap();
+
+    for (row, text) in line.text.lines().enumerate() {
+        writeln!(
+            svg,
+            r#"<tspan x="0" y="{}">{}</tspan>"#,
+            row as f32 * ROW_HEIGHT * layout.font_size,
+            escape(text)
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</text>\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn hex_color(color: Color32) -> String {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
@@ -0,0 +1,161 @@
+use egui::Pos2;
+
+use crate::{Line, Shape};
+
+impl Line {
+    /// Distance from `pos` to the closest segment of the line as it is drawn.
+    ///
+    /// Text counts from anywhere inside its box.
+    pub fn distance(&self, pos: Pos2) -> f32 {
+        let paths = self.paths();
+
+        if self.shape == Shape::Text && polygon_contains(&paths[0], pos) {
+            return 0.0;
+        }
+
+        paths
+            .iter()
+            .map(|path| match path.len() {
+                0 => f32::INFINITY,
+                1 => path[0].distance(pos),
+                _ => path
+                    .windows(2)
+                    .map(|segment| distance_to_segment(pos, segment[0], segment[1]))
+                    .fold(f32::INFINITY, f32::min),
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Cuts away the parts of the line within `radius` of `center`, `None` if nothing is cut.
+    ///
+    /// The rest comes back as freehand pieces on the same layer, shapes are cut along their
+    /// outline. Text cannot be cut and is erased whole.
+    pub fn erase(&self, center: Pos2, radius: f32) -> Option<Vec<Line>> {
+        if self.shape == Shape::Text || self.len() < 2 {
+            return (self.distance(center) <= radius).then(Vec::new);
+        }
+
+        let paths = self.paths();
+
+        let erased: Vec<Option<Vec<Vec<Pos2>>>> = paths
+            .iter()
+            .map(|path| erase_path(path, center, radius))
+            .collect();
+
+        if erased.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let pieces = paths
+            .into_iter()
+            .zip(erased)
+            .flat_map(|(path, erased)| erased.unwrap_or_else(|| vec![path]))
+            .map(|points| Line {
+                points,
+                layer: self.layer,
+                ..Line::new(self.stroke)
+            })
+            .collect();
+
+        Some(pieces)
+    }
+}
+
+/// The parts of the polyline `path` outside the circle around `center`, `None` if none of it is inside.
+fn erase_path(path: &[Pos2], center: Pos2, radius: f32) -> Option<Vec<Vec<Pos2>>> {
+    let mut pieces = Vec::new();
+    let mut piece: Vec<Pos2> = Vec::new();
+    let mut cut = false;
+
+    for segment in path.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+
+        match circle_overlap(a, b, center, radius) {
+            None => {
+                if piece.is_empty() {
+                    piece.push(a);
+                }
+
+                piece.push(b);
+            }
+            Some((enter, exit)) => {
+                cut = true;
+
+                if enter > 0.0 {
+                    if piece.is_empty() {
+                        piece.push(a);
+                    }
+
+                    piece.push(a.lerp(b, enter));
+                }
+
+                pieces.push(std::mem::take(&mut piece));
+
+                if exit < 1.0 {
+                    piece.extend([a.lerp(b, exit), b]);
+                }
+            }
+        }
+    }
+
+    pieces.push(piece);
+    pieces.retain(|piece| piece.len() >= 2);
+
+    cut.then_some(pieces)
+}
+
+/// Where the segment from `a` to `b` is inside the circle, as fractions of the way from `a` to `b`.
+///
+/// Barely touching does not count, so the ends of pieces cut before are not cut again.
+fn circle_overlap(a: Pos2, b: Pos2, center: Pos2, radius: f32) -> Option<(f32, f32)> {
+    let ab = b - a;
+    let ca = a - center;
+
+    let qa = ab.length_sq();
+    let qb = 2.0 * ca.dot(ab);
+    let qc = ca.length_sq() - radius * radius;
+
+    if qa == 0.0 {
+        return (qc <= 0.0).then_some((0.0, 1.0));
+    }
+
+    let discriminant = qb * qb - 4.0 * qa * qc;
+
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+
+    let enter = ((-qb - root) / (2.0 * qa)).max(0.0);
+    let exit = ((-qb + root) / (2.0 * qa)).min(1.0);
+
+    ((exit - enter) * qa.sqrt() > radius * 1e-3).then_some((enter, exit))
+}
+
+fn distance_to_segment(pos: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let ab = b - a;
+
+    let t = match ab.length_sq() > 0.0 {
+        true => ((pos - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+
+    (a + ab * t).distance(pos)
+}
+
+/// Whether `pos` is inside the polygon with the corners `outline`, by counting crossed edges.
+pub fn polygon_contains(outline: &[Pos2], pos: Pos2) -> bool {
+    let mut inside = false;
+
+    for (i, a) in outline.iter().enumerate() {
+        let b = outline[(i + 1) % outline.len()];
+
+        if (a.y > pos.y) != (b.y > pos.y) && pos.x < a.x + (pos.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
@@ -0,0 +1,61 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Timestamp;
+
+/// Id of a line, the [`Timestamp`] it was created at.
+///
+/// Ids sort like the timestamps they are made of, so lines are kept in the order they were
+/// created. Sent as `"counter.client"`, because JSON objects are keyed by strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineId {
+    pub counter: u64,
+    pub client: u64,
+}
+
+impl From<Timestamp> for LineId {
+    fn from(timestamp: Timestamp) -> Self {
+        Self {
+            counter: timestamp.counter,
+            client: timestamp.client,
+        }
+    }
+}
+
+impl fmt::Display for LineId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.counter, self.client)
+    }
+}
+
+impl FromStr for LineId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let (counter, client) = s.split_once('.')?;
+
+            Some(Self {
+                counter: counter.parse().ok()?,
+                client: client.parse().ok()?,
+            })
+        };
+
+        parse().ok_or_else(|| format!("Invalid line id {:?}", s))
+    }
+}
+
+impl Serialize for LineId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LineId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// Layer every line is on unless it says otherwise, it exists on every board.
+pub const DEFAULT_LAYER: u64 = 0;
+
+/// A named group of lines that is drawn, hidden and locked together.
+///
+/// Layers are drawn from the lowest to the highest `order`, the lines on a layer in the order they
+/// were created, see [`crate::Lines::drawing_order`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    pub order: i64,
+    /// Hidden layers are not drawn or exported.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Lines on locked layers cannot be drawn, selected or erased.
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl Layer {
+    pub fn new(name: String, order: i64) -> Self {
+        Self {
+            name,
+            order,
+            hidden: false,
+            locked: false,
+        }
+    }
+
+    /// Whether lines on the layer can be changed by hand.
+    pub fn is_editable(&self) -> bool {
+        !self.hidden && !self.locked
+    }
+}
+
+impl Default for Layer {
+    /// The [`DEFAULT_LAYER`] as it is until someone changes it.
+    fn default() -> Self {
+        Self::new("Layer 1".to_string(), 0)
+    }
+}
+
+pub(crate) fn is_default_layer(layer: &u64) -> bool {
+    *layer == DEFAULT_LAYER
+}
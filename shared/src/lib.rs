@@ -1,12 +1,60 @@
-use std::collections::BTreeMap;
+mod clock;
+mod cursor;
+mod export;
+mod geometry;
+mod id;
+mod layer;
+mod lines;
+mod operation;
+mod presence;
+mod shape;
+mod text;
 
 use egui::{Pos2, Stroke};
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Clone, Serialize, Deserialize)]
+pub use clock::{Clock, Timestamp};
+pub use cursor::{Cursor, Event};
+pub use export::{export_size, export_svg, png_size, EXPORT_SIZE_WITHOUT_BACKGROUND};
+pub use geometry::polygon_contains;
+pub use id::LineId;
+pub use layer::{Layer, DEFAULT_LAYER};
+pub use lines::Lines;
+pub use operation::{Envelope, Operation, Transform, PROTOCOL_VERSION};
+pub use presence::{display_name, participant_color, Hello, Participant, Presence, MAX_NAME_LEN};
+pub use shape::Shape;
+pub use text::{TextLayout, ROW_HEIGHT};
+
+/// Board used when a client does not ask for a specific one.
+pub const DEFAULT_BOARD: &str = "default";
+
+const MAX_BOARD_ID_LEN: usize = 64;
+
+/// Board ids end up in urls and directory names, so only a safe subset of characters is allowed.
+pub fn is_valid_board_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_BOARD_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Line {
     pub points: Vec<Pos2>,
     pub stroke: Stroke,
+    /// Left out for freehand lines, so they look the same as before shapes existed.
+    #[serde(default, skip_serializing_if = "Shape::is_freehand")]
+    pub shape: Shape,
+    /// Content of a [`Shape::Text`], empty for every other shape.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    /// Id of the [`Layer`] the line is on.
+    #[serde(default, skip_serializing_if = "layer::is_default_layer")]
+    pub layer: u64,
+    /// Id of the line the eraser cut this piece from, it is drawn in that line's place.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cut_from: Option<LineId>,
 }
 
 impl Line {
@@ -14,21 +62,41 @@ impl Line {
         Self {
             points: Vec::new(),
             stroke,
+            shape: Shape::Freehand,
+            text: String::new(),
+            layer: DEFAULT_LAYER,
+            cut_from: None,
         }
     }
 
+    /// Id that places the line `id` among the other lines on its layer.
+    ///
+    /// Pieces left by the eraser take the place of the line they were cut from, instead of
+    /// jumping above every line drawn after it.
+    pub fn place(&self, id: LineId) -> LineId {
+        self.cut_from.unwrap_or(id)
+    }
+
     pub fn from_canvas(&mut self, canvas_rect: &egui::Rect) {
-        for pos in self.points.iter_mut() {
-            pos.x = (pos.x - canvas_rect.min.x) / canvas_rect.width();
-            pos.y = (pos.y - canvas_rect.min.y) / canvas_rect.height();
-        }
+        points_from_canvas(&mut self.points, canvas_rect);
     }
 
     pub fn to_canvas(&mut self, canvas_rect: &egui::Rect) {
-        for pos in self.points.iter_mut() {
-            pos.x = pos.x * canvas_rect.width() + canvas_rect.min.x;
-            pos.y = pos.y * canvas_rect.height() + canvas_rect.min.y;
-        }
+        points_to_canvas(&mut self.points, canvas_rect);
+    }
+}
+
+fn points_from_canvas(points: &mut [Pos2], canvas_rect: &egui::Rect) {
+    for pos in points.iter_mut() {
+        pos.x = (pos.x - canvas_rect.min.x) / canvas_rect.width();
+        pos.y = (pos.y - canvas_rect.min.y) / canvas_rect.height();
+    }
+}
+
+fn points_to_canvas(points: &mut [Pos2], canvas_rect: &egui::Rect) {
+    for pos in points.iter_mut() {
+        pos.x = pos.x * canvas_rect.width() + canvas_rect.min.x;
+        pos.y = pos.y * canvas_rect.height() + canvas_rect.min.y;
     }
 }
 
@@ -46,59 +114,32 @@ impl std::ops::DerefMut for Line {
     }
 }
 
+/// The changes of a board after some revision, as returned by `GET /boards/{id}/lines?since=`.
 #[derive(Default, Clone, Serialize, Deserialize)]
-pub struct Lines(pub BTreeMap<u64, Line>);
-
-impl Lines {
-    pub fn update_from_other(&mut self, other: Lines) {
-        self.0.extend(other.0);
-    }
-
-    pub fn from_canvas(&mut self, canvas_rect: &egui::Rect) {
-        for line in self.0.values_mut() {
-            line.from_canvas(canvas_rect);
-        }
-    }
-
-    pub fn to_canvas(&mut self, canvas_rect: &egui::Rect) {
-        for line in self.0.values_mut() {
-            line.to_canvas(canvas_rect);
-        }
-    }
+pub struct Delta {
+    /// Revision of the board this delta brings the client to.
+    pub revision: u64,
+    /// `lines` is the whole board, because the client asked for all of it or for a revision the
+    /// board does not have.
+    pub full: bool,
+    /// State of the lines that changed, including removed ones.
+    pub lines: Lines,
 }
 
-impl FromIterator<(u64, Line)> for Lines {
-    fn from_iter<T: IntoIterator<Item = (u64, Line)>>(iter: T) -> Self {
-        let mut lines = Lines::default();
-        for (id, line) in iter {
-            lines.0.insert(id, line);
-        }
-        lines
+impl Delta {
+    /// Merges the delta into `lines`, a full one too.
+    ///
+    /// Lines the board had when the delta was made may have changed since, merging keeps those
+    /// changes where replacing the lines would lose them.
+    pub fn apply(self, lines: &mut Lines) {
+        lines.merge(self.lines);
     }
 }
 
-impl std::ops::Deref for Lines {
-    type Target = BTreeMap<u64, Line>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl std::ops::DerefMut for Lines {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-impl ToString for Lines {
-    fn to_string(&self) -> String {
-        serde_json::to_string(self).unwrap()
-    }
-}
+impl std::str::FromStr for Delta {
+    type Err = serde_json::Error;
 
-impl From<String> for Lines {
-    fn from(s: String) -> Self {
-        serde_json::from_str(&s).unwrap()
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
     }
 }
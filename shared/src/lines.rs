@@ -0,0 +1,463 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use egui::Pos2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    points_from_canvas, points_to_canvas, Layer, Line, LineId, Operation, Timestamp, Transform,
+    DEFAULT_LAYER,
+};
+
+/// Everything known about a single line id.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    /// When the current version of the line was added or removed.
+    timestamp: Timestamp,
+    /// `None` if the line was removed or has not arrived yet.
+    line: Option<Line>,
+    /// Appended points that arrived before the points in front of them, by offset.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pending: BTreeMap<usize, Vec<Pos2>>,
+    /// Transforms newer than `timestamp`, sorted by their timestamp.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    transforms: Vec<(Timestamp, Transform)>,
+}
+
+impl Entry {
+    fn new(timestamp: Timestamp, line: Option<Line>) -> Self {
+        Self {
+            timestamp,
+            line,
+            pending: BTreeMap::new(),
+            transforms: Vec::new(),
+        }
+    }
+
+    fn merge(&mut self, other: Entry) {
+        match other.timestamp.cmp(&self.timestamp) {
+            Ordering::Greater => {
+                let transforms = std::mem::take(&mut self.transforms);
+
+                *self = other;
+
+                self.merge_transforms(transforms);
+            }
+            Ordering::Equal => {
+                match (&mut self.line, other.line) {
+                    // Both are the same line by the same author, one just has more of its points.
+                    (Some(line), Some(other_line)) => {
+                        if other_line.len() > line.len() {
+                            line.points = other_line.points;
+                        }
+                    }
+                    (line @ Some(_), None) => *line = None,
+                    _ => {}
+                }
+
+                for (offset, points) in other.pending {
+                    self.add_pending(offset, points);
+                }
+
+                self.merge_transforms(other.transforms);
+            }
+            Ordering::Less => self.merge_transforms(other.transforms),
+        }
+    }
+
+    fn merge_transforms(&mut self, transforms: Vec<(Timestamp, Transform)>) {
+        for (timestamp, transform) in transforms {
+            if let Err(index) = self
+                .transforms
+                .binary_search_by_key(&timestamp, |(timestamp, _)| *timestamp)
+            {
+                self.transforms.insert(index, (timestamp, transform));
+            }
+        }
+    }
+
+    fn add_pending(&mut self, offset: usize, points: Vec<Pos2>) {
+        let pending = self.pending.entry(offset).or_default();
+
+        if points.len() > pending.len() {
+            *pending = points;
+        }
+    }
+
+    /// Brings the entry into the one form every replica agrees on.
+    fn normalize(&mut self, cleared: Timestamp) {
+        if self.timestamp < cleared {
+            self.timestamp = Timestamp::default();
+            self.line = None;
+        }
+
+        match &mut self.line {
+            Some(line) => {
+                while let Some(entry) = self.pending.first_entry() {
+                    if *entry.key() > line.len() {
+                        break;
+                    }
+
+                    let offset = *entry.key();
+                    let points = entry.remove();
+
+                    if offset + points.len() > line.len() {
+                        let known = line.len() - offset;
+                        line.extend_from_slice(&points[known..]);
+                    }
+                }
+            }
+            None => self.pending.clear(),
+        }
+
+        let timestamp = self.timestamp;
+
+        self.transforms.retain(|(transform_timestamp, _)| {
+            *transform_timestamp > timestamp && *transform_timestamp >= cleared
+        });
+    }
+
+    /// Nothing worth remembering, the id might as well be unknown.
+    fn is_empty(&self) -> bool {
+        self.timestamp == Timestamp::default() && self.line.is_none() && self.transforms.is_empty()
+    }
+
+    /// The line as it is drawn, with all transforms applied.
+    fn visible(&self) -> Option<Line> {
+        let mut line = self.line.clone()?;
+
+        for (_, transform) in &self.transforms {
+            for pos in line.iter_mut() {
+                *pos = transform.apply(*pos);
+            }
+        }
+
+        Some(line)
+    }
+
+    fn convert(
+        &mut self,
+        convert_points: impl Fn(&mut [Pos2]),
+        convert_transform: impl Fn(Transform) -> Transform,
+    ) {
+        if let Some(line) = &mut self.line {
+            convert_points(&mut line.points);
+        }
+
+        for points in self.pending.values_mut() {
+            convert_points(points);
+        }
+
+        for (_, transform) in self.transforms.iter_mut() {
+            *transform = convert_transform(*transform);
+        }
+    }
+}
+
+/// The lines of a board as a conflict-free replicated data type.
+///
+/// Every line id is a last-writer-wins register ordered by [`Timestamp`], where a removal is a
+/// tombstone and wins against an add with the same timestamp. Points streamed while drawing are
+/// kept by offset, transforms are applied in timestamp order and a clear drops everything older
+/// than it. Layers are last-writer-wins registers too, a clear keeps them. Applying the same
+/// operations or merging the same states in any order, any number of times, gives the same lines.
+///
+/// Derefs to the visible lines, sorted by id and so by when they were created.
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "LinesState")]
+pub struct Lines {
+    entries: BTreeMap<LineId, Entry>,
+    /// Everything older than this was cleared.
+    cleared: Timestamp,
+    /// Every layer that was set, with the timestamp of its current version.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    layers: BTreeMap<u64, (Timestamp, Layer)>,
+    #[serde(skip)]
+    visible: BTreeMap<LineId, Line>,
+}
+
+#[derive(Deserialize)]
+struct LinesState {
+    entries: BTreeMap<LineId, Entry>,
+    #[serde(default)]
+    cleared: Timestamp,
+    #[serde(default)]
+    layers: BTreeMap<u64, (Timestamp, Layer)>,
+}
+
+impl From<LinesState> for Lines {
+    fn from(state: LinesState) -> Self {
+        let mut lines = Lines {
+            cleared: state.cleared,
+            layers: state.layers,
+            ..Default::default()
+        };
+
+        for (id, entry) in state.entries {
+            lines.update(id, entry);
+        }
+
+        lines
+    }
+}
+
+impl Lines {
+    /// Applies `operation`, this is the only way boards are changed on the backend and the frontend.
+    pub fn apply(&mut self, operation: Operation) {
+        match operation {
+            Operation::AddLine {
+                id,
+                line,
+                timestamp,
+            } => {
+                self.merge_entry(id, Entry::new(timestamp, Some(line)));
+            }
+            Operation::AppendPoints {
+                id,
+                timestamp,
+                stroke,
+                layer,
+                offset,
+                points,
+            } => {
+                let line = Line {
+                    layer,
+                    ..Line::new(stroke)
+                };
+
+                let mut entry = Entry::new(timestamp, Some(line));
+                entry.pending.insert(offset, points);
+
+                self.merge_entry(id, entry);
+            }
+            Operation::RemoveLines { ids, timestamp } => {
+                for id in ids {
+                    self.merge_entry(id, Entry::new(timestamp, None));
+                }
+            }
+            Operation::Clear { timestamp } => self.clear_before(timestamp),
+            Operation::Transform {
+                ids,
+                transform,
+                timestamp,
+            } => {
+                for id in ids {
+                    let mut entry = Entry::new(Timestamp::default(), None);
+                    entry.transforms.push((timestamp, transform));
+
+                    self.merge_entry(id, entry);
+                }
+            }
+            Operation::SetLayer {
+                id,
+                layer,
+                timestamp,
+            } => self.merge_layer(id, timestamp, layer),
+        }
+    }
+
+    /// Merges the state of another replica into this one.
+    pub fn merge(&mut self, other: Lines) {
+        self.clear_before(other.cleared);
+
+        for (id, entry) in other.entries {
+            self.merge_entry(id, entry);
+        }
+
+        for (id, (timestamp, layer)) in other.layers {
+            self.merge_layer(id, timestamp, layer);
+        }
+    }
+
+    /// The state of the lines with the given ids, to be merged into another replica.
+    ///
+    /// There are only a few layers, so all of them are part of every subset.
+    pub fn subset(&self, ids: impl IntoIterator<Item = LineId>) -> Lines {
+        let mut lines = Lines {
+            cleared: self.cleared,
+            layers: self.layers.clone(),
+            ..Default::default()
+        };
+
+        for id in ids {
+            if let Some(entry) = self.entries.get(&id) {
+                lines.update(id, entry.clone());
+            }
+        }
+
+        lines
+    }
+
+    /// Whether anything is known about `id`, including that it was removed.
+    pub fn contains_state(&self, id: &LineId) -> bool {
+        self.entries.contains_key(id)
+    }
+
+    /// The newest timestamp in these lines, to move a [`crate::Clock`] past it.
+    pub fn latest_timestamp(&self) -> Timestamp {
+        self.entries
+            .values()
+            .flat_map(|entry| {
+                std::iter::once(entry.timestamp)
+                    .chain(entry.transforms.iter().map(|(timestamp, _)| *timestamp))
+            })
+            .chain(self.layers.values().map(|(timestamp, _)| *timestamp))
+            .fold(self.cleared, Timestamp::max)
+    }
+
+    /// The layers from the bottom to the top, including the [`DEFAULT_LAYER`].
+    pub fn layers(&self) -> Vec<(u64, Layer)> {
+        let mut layers: Vec<(u64, Layer)> = self
+            .layers
+            .iter()
+            .map(|(id, (_, layer))| (*id, layer.clone()))
+            .collect();
+
+        if !self.layers.contains_key(&DEFAULT_LAYER) {
+            layers.push((DEFAULT_LAYER, Layer::default()));
+        }
+
+        layers.sort_by_key(|(id, layer)| (layer.order, *id));
+
+        layers
+    }
+
+    /// `None` for the default layer until it is changed and for layers that have not arrived yet,
+    /// both are drawn like [`Layer::default`].
+    pub fn layer(&self, id: u64) -> Option<&Layer> {
+        self.layers.get(&id).map(|(_, layer)| layer)
+    }
+
+    /// Whether the line `id` is visible and on a layer that is neither hidden nor locked.
+    pub fn is_editable(&self, id: &LineId) -> bool {
+        self.visible
+            .get(id)
+            .is_some_and(|line| self.layer(line.layer).map_or(true, Layer::is_editable))
+    }
+
+    /// The visible lines on layers that are not hidden, from the bottom to the top.
+    ///
+    /// Layers are stacked by their order and the lines on a layer by their id, which follows the
+    /// order they were created in, see [`Timestamp::id`]. Pieces left by the eraser are drawn in
+    /// place of the line they were cut from, see [`Line::place`]. Every replica draws the same
+    /// lines in the same order.
+    pub fn drawing_order(&self) -> Vec<(LineId, &Line)> {
+        let mut lines: Vec<(LineId, &Line)> = self
+            .visible
+            .iter()
+            .filter(|(_, line)| !self.layer(line.layer).is_some_and(|layer| layer.hidden))
+            .map(|(id, line)| (*id, line))
+            .collect();
+
+        // Stable, so pieces of the same line stay in the order of their ids.
+        lines.sort_by_cached_key(|(id, line)| {
+            let order = self.layer(line.layer).map_or(0, |layer| layer.order);
+
+            (order, line.layer, line.place(*id))
+        });
+
+        lines
+    }
+
+    pub fn from_canvas(&mut self, canvas_rect: &egui::Rect) {
+        self.convert(
+            |points| points_from_canvas(points, canvas_rect),
+            |transform| transform.from_canvas(canvas_rect),
+        );
+    }
+
+    pub fn to_canvas(&mut self, canvas_rect: &egui::Rect) {
+        self.convert(
+            |points| points_to_canvas(points, canvas_rect),
+            |transform| transform.to_canvas(canvas_rect),
+        );
+    }
+
+    fn convert(
+        &mut self,
+        convert_points: impl Fn(&mut [Pos2]),
+        convert_transform: impl Fn(Transform) -> Transform,
+    ) {
+        let entries = std::mem::take(&mut self.entries);
+
+        self.visible.clear();
+
+        for (id, mut entry) in entries {
+            entry.convert(&convert_points, &convert_transform);
+
+            self.update(id, entry);
+        }
+    }
+
+    fn clear_before(&mut self, timestamp: Timestamp) {
+        if timestamp <= self.cleared {
+            return;
+        }
+
+        self.cleared = timestamp;
+
+        let entries = std::mem::take(&mut self.entries);
+
+        for (id, mut entry) in entries {
+            entry.normalize(self.cleared);
+
+            self.update(id, entry);
+        }
+    }
+
+    fn merge_layer(&mut self, id: u64, timestamp: Timestamp, layer: Layer) {
+        match self.layers.get(&id) {
+            Some((current, _)) if *current >= timestamp => {}
+            _ => {
+                self.layers.insert(id, (timestamp, layer));
+            }
+        }
+    }
+
+    fn merge_entry(&mut self, id: LineId, entry: Entry) {
+        let mut entry = match self.entries.remove(&id) {
+            Some(mut current) => {
+                current.merge(entry);
+                current
+            }
+            None => entry,
+        };
+
+        entry.normalize(self.cleared);
+
+        self.update(id, entry);
+    }
+
+    /// Stores `entry` and refreshes the visible line of `id`.
+    fn update(&mut self, id: LineId, entry: Entry) {
+        match entry.visible() {
+            Some(line) => self.visible.insert(id, line),
+            None => self.visible.remove(&id),
+        };
+
+        if !entry.is_empty() {
+            self.entries.insert(id, entry);
+        }
+    }
+}
+
+impl std::ops::Deref for Lines {
+    type Target = BTreeMap<LineId, Line>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.visible
+    }
+}
+
+impl std::fmt::Display for Lines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(self).map_err(|_| std::fmt::Error)?)
+    }
+}
+
+impl std::str::FromStr for Lines {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
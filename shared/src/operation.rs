@@ -0,0 +1,280 @@
+use egui::{Pos2, Stroke, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{layer, points_from_canvas, points_to_canvas, Layer, Line, LineId, Timestamp};
+
+/// Version of the operation protocol, bumped on every incompatible change.
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// A single change to a board.
+///
+/// Clients send operations to the backend, which journals them and pushes them to every
+/// subscriber of the board. Points are in normalized canvas coordinates, see [`Line::from_canvas`].
+///
+/// Every operation carries the [`Timestamp`] it was made at, which decides how it merges with
+/// concurrent ones, see [`crate::Lines`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Operation {
+    /// Adds a line or replaces an older line with the same id.
+    ///
+    /// Sending the same line again with the same timestamp and more points extends it.
+    AddLine {
+        id: LineId,
+        line: Line,
+        #[serde(default)]
+        timestamp: Timestamp,
+    },
+    /// Appends points to a line that is still being drawn.
+    AppendPoints {
+        id: LineId,
+        /// Timestamp of the line the points belong to.
+        #[serde(default)]
+        timestamp: Timestamp,
+        /// Stroke of the line, in case the points arrive before the line.
+        #[serde(default)]
+        stroke: Stroke,
+        /// Layer of the line, for the same reason.
+        #[serde(default, skip_serializing_if = "layer::is_default_layer")]
+        layer: u64,
+        /// Index of the first point in the line.
+        #[serde(default)]
+        offset: usize,
+        points: Vec<Pos2>,
+    },
+    RemoveLines {
+        ids: Vec<LineId>,
+        #[serde(default)]
+        timestamp: Timestamp,
+    },
+    /// Removes every line older than the clear, on every layer.
+    ///
+    /// Clients only send it while no layer is hidden or locked, otherwise they remove the lines
+    /// that can be edited one by one.
+    Clear {
+        #[serde(default)]
+        timestamp: Timestamp,
+    },
+    /// Moves, scales or rotates the points of lines.
+    Transform {
+        ids: Vec<LineId>,
+        transform: Transform,
+        #[serde(default)]
+        timestamp: Timestamp,
+    },
+    /// Adds a layer or replaces an older version of it, like when it is renamed or hidden.
+    SetLayer {
+        id: u64,
+        layer: Layer,
+        #[serde(default)]
+        timestamp: Timestamp,
+    },
+}
+
+impl Operation {
+    pub fn timestamp(&self) -> Timestamp {
+        match self {
+            Operation::AddLine { timestamp, .. }
+            | Operation::AppendPoints { timestamp, .. }
+            | Operation::RemoveLines { timestamp, .. }
+            | Operation::Clear { timestamp }
+            | Operation::Transform { timestamp, .. }
+            | Operation::SetLayer { timestamp, .. } => *timestamp,
+        }
+    }
+
+    /// Gives the operation a new timestamp, to send it again as a new change.
+    pub fn set_timestamp(&mut self, new_timestamp: Timestamp) {
+        match self {
+            Operation::AddLine { timestamp, .. }
+            | Operation::AppendPoints { timestamp, .. }
+            | Operation::RemoveLines { timestamp, .. }
+            | Operation::Clear { timestamp }
+            | Operation::Transform { timestamp, .. }
+            | Operation::SetLayer { timestamp, .. } => *timestamp = new_timestamp,
+        }
+    }
+
+    pub fn from_canvas(&mut self, canvas_rect: &egui::Rect) {
+        match self {
+            Operation::AddLine { line, .. } => line.from_canvas(canvas_rect),
+            Operation::AppendPoints { points, .. } => points_from_canvas(points, canvas_rect),
+            Operation::Transform { transform, .. } => {
+                *transform = transform.from_canvas(canvas_rect);
+            }
+            Operation::RemoveLines { .. }
+            | Operation::Clear { .. }
+            | Operation::SetLayer { .. } => {}
+        }
+    }
+
+    pub fn to_canvas(&mut self, canvas_rect: &egui::Rect) {
+        match self {
+            Operation::AddLine { line, .. } => line.to_canvas(canvas_rect),
+            Operation::AppendPoints { points, .. } => points_to_canvas(points, canvas_rect),
+            Operation::Transform { transform, .. } => {
+                *transform = transform.to_canvas(canvas_rect);
+            }
+            Operation::RemoveLines { .. }
+            | Operation::Clear { .. }
+            | Operation::SetLayer { .. } => {}
+        }
+    }
+}
+
+/// Operations as they are sent over the wire, tagged with the protocol version.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u32,
+    pub operations: Vec<Operation>,
+}
+
+impl Envelope {
+    pub fn new(operations: Vec<Operation>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            operations,
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.version == PROTOCOL_VERSION
+    }
+}
+
+/// Affine transform of points: `x' = a * x + b * y + tx` and `y' = c * x + d * y + ty`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub tx: f32,
+    pub ty: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        tx: 0.0,
+        ty: 0.0,
+    };
+
+    pub fn translate(delta: Vec2) -> Self {
+        Self {
+            tx: delta.x,
+            ty: delta.y,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scale_about(origin: Pos2, scale: Vec2) -> Self {
+        Self::translate(-origin.to_vec2())
+            .then(Self {
+                a: scale.x,
+                d: scale.y,
+                ..Self::IDENTITY
+            })
+            .then(Self::translate(origin.to_vec2()))
+    }
+
+    /// Rotates by `angle` radians around `origin`.
+    pub fn rotate_about(origin: Pos2, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+
+        Self::translate(-origin.to_vec2())
+            .then(Self {
+                a: cos,
+                b: -sin,
+                c: sin,
+                d: cos,
+                ..Self::IDENTITY
+            })
+            .then(Self::translate(origin.to_vec2()))
+    }
+
+    /// The transform that applies `self` first and `next` after it.
+    pub fn then(self, next: Self) -> Self {
+        Self {
+            a: next.a * self.a + next.b * self.c,
+            b: next.a * self.b + next.b * self.d,
+            c: next.c * self.a + next.d * self.c,
+            d: next.c * self.b + next.d * self.d,
+            tx: next.a * self.tx + next.b * self.ty + next.tx,
+            ty: next.c * self.tx + next.d * self.ty + next.ty,
+        }
+    }
+
+    /// `None` if the transform collapses points onto a line.
+    pub fn inverse(self) -> Option<Self> {
+        let determinant = self.a * self.d - self.b * self.c;
+
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let a = self.d / determinant;
+        let b = -self.b / determinant;
+        let c = -self.c / determinant;
+        let d = self.a / determinant;
+
+        Some(Self {
+            a,
+            b,
+            c,
+            d,
+            tx: -(a * self.tx + b * self.ty),
+            ty: -(c * self.tx + d * self.ty),
+        })
+    }
+
+    pub fn apply(&self, pos: Pos2) -> Pos2 {
+        Pos2::new(
+            self.a * pos.x + self.b * pos.y + self.tx,
+            self.c * pos.x + self.d * pos.y + self.ty,
+        )
+    }
+
+    /// Turns a transform in canvas coordinates into the same transform in normalized coordinates.
+    pub fn from_canvas(self, canvas_rect: &egui::Rect) -> Self {
+        let (to_canvas, from_canvas) = canvas_transforms(canvas_rect);
+
+        to_canvas.then(self).then(from_canvas)
+    }
+
+    /// Turns a transform in normalized coordinates into the same transform in canvas coordinates.
+    pub fn to_canvas(self, canvas_rect: &egui::Rect) -> Self {
+        let (to_canvas, from_canvas) = canvas_transforms(canvas_rect);
+
+        from_canvas.then(self).then(to_canvas)
+    }
+}
+
+/// The transforms from normalized to canvas coordinates and back.
+fn canvas_transforms(canvas_rect: &egui::Rect) -> (Transform, Transform) {
+    let to_canvas = Transform {
+        a: canvas_rect.width(),
+        d: canvas_rect.height(),
+        tx: canvas_rect.min.x,
+        ty: canvas_rect.min.y,
+        ..Transform::IDENTITY
+    };
+
+    let from_canvas = Transform {
+        a: 1.0 / canvas_rect.width(),
+        d: 1.0 / canvas_rect.height(),
+        tx: -canvas_rect.min.x / canvas_rect.width(),
+        ty: -canvas_rect.min.y / canvas_rect.height(),
+        ..Transform::IDENTITY
+    };
+
+    (to_canvas, from_canvas)
+}
@@ -0,0 +1,77 @@
+use egui::{ecolor::Hsva, Color32};
+use serde::{Deserialize, Serialize};
+
+/// Longest display name in characters, longer ones are cut.
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Name shown for clients that did not give one.
+const ANONYMOUS: &str = "Anonymous";
+
+/// Colors handed out to participants first, picked to be told apart easily.
+const PALETTE: [Color32; 12] = [
+    Color32::from_rgb(0xe6, 0x19, 0x4b),
+    Color32::from_rgb(0x43, 0x63, 0xd8),
+    Color32::from_rgb(0x3c, 0xb4, 0x4b),
+    Color32::from_rgb(0xf5, 0x82, 0x31),
+    Color32::from_rgb(0x91, 0x1e, 0xb4),
+    Color32::from_rgb(0x42, 0xd4, 0xf4),
+    Color32::from_rgb(0xf0, 0x32, 0xe6),
+    Color32::from_rgb(0xbf, 0xef, 0x45),
+    Color32::from_rgb(0x46, 0x99, 0x90),
+    Color32::from_rgb(0x9a, 0x63, 0x24),
+    Color32::from_rgb(0x80, 0x00, 0x00),
+    Color32::from_rgb(0x00, 0x00, 0x75),
+];
+
+/// Sent regularly by a client to stay on the list of who is on a board, see `POST /boards/{id}/presence`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hello {
+    /// Session from an earlier [`Presence`], a new one is assigned if missing.
+    #[serde(default)]
+    pub session: Option<u64>,
+    pub name: String,
+}
+
+/// A client that is on a board.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Participant {
+    pub session: u64,
+    pub name: String,
+    /// Assigned by the backend, no other participant on the board has the same one.
+    pub color: Color32,
+    /// Milliseconds since the Unix epoch of the last [`Hello`].
+    pub last_seen: u64,
+}
+
+/// Answer to a [`Hello`], with everyone on the board including the client itself.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Presence {
+    pub session: u64,
+    /// Color of the client, meant as its default stroke color.
+    pub color: Color32,
+    pub participants: Vec<Participant>,
+}
+
+/// Trims `name` and cuts it to [`MAX_NAME_LEN`] characters, falling back to a placeholder if nothing is left.
+pub fn display_name(name: &str) -> String {
+    let name: String = name.trim().chars().take(MAX_NAME_LEN).collect();
+
+    match name.trim_end() {
+        "" => ANONYMOUS.to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// The `index`th color handed out to participants, all of them differ.
+///
+/// Once the palette is used up, hues are spread by the golden angle so neighbours stay apart.
+pub fn participant_color(index: usize) -> Color32 {
+    match PALETTE.get(index) {
+        Some(color) => *color,
+        None => {
+            let hue = ((index - PALETTE.len()) as f32 * 0.618_034).fract();
+
+            Hsva::new(hue, 0.75, 0.85, 1.0).into()
+        }
+    }
+}
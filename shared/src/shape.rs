@@ -0,0 +1,117 @@
+use std::f32::consts::TAU;
+
+use egui::{Pos2, Rect};
+use serde::{Deserialize, Serialize};
+
+use crate::Line;
+
+/// Segments an ellipse is drawn with.
+const ELLIPSE_SEGMENTS: usize = 64;
+
+/// What a [`Line`] draws with its points.
+///
+/// Every shape is kept as points that any [`crate::Transform`] maps to the transformed shape, so
+/// shapes can be moved, scaled and rotated like freehand lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Shape {
+    /// A polyline through all points.
+    #[default]
+    Freehand,
+    /// From the first to the second point.
+    Line,
+    /// Through its four corners.
+    Rectangle,
+    /// Around the first point, the other two are the ends of its half axes.
+    Ellipse,
+    /// From the first to the second point, with a head from the third over the second to the fourth point.
+    Arrow,
+    /// [`Line::text`] placed by the first two points, see [`Line::new_text`].
+    Text,
+}
+
+impl Shape {
+    /// The shapes that are drawn by dragging.
+    pub const ALL: [Shape; 5] = [
+        Shape::Freehand,
+        Shape::Line,
+        Shape::Rectangle,
+        Shape::Ellipse,
+        Shape::Arrow,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Freehand => "Freehand",
+            Shape::Line => "Line",
+            Shape::Rectangle => "Rectangle",
+            Shape::Ellipse => "Ellipse",
+            Shape::Arrow => "Arrow",
+            Shape::Text => "Text",
+        }
+    }
+
+    pub fn is_freehand(&self) -> bool {
+        *self == Shape::Freehand
+    }
+
+    /// The points of the shape dragged from `start` to `end`, the arrow head grows with `width`.
+    pub fn points(self, start: Pos2, end: Pos2, width: f32) -> Vec<Pos2> {
+        let rect = Rect::from_two_pos(start, end);
+
+        match self {
+            Shape::Freehand | Shape::Line | Shape::Text => vec![start, end],
+            Shape::Rectangle => vec![
+                rect.left_top(),
+                rect.right_top(),
+                rect.right_bottom(),
+                rect.left_bottom(),
+            ],
+            Shape::Ellipse => vec![rect.center(), rect.right_center(), rect.center_bottom()],
+            Shape::Arrow => {
+                let direction = (end - start).normalized();
+                let length = (4.0 * width + 6.0).min((end - start).length() / 2.0);
+
+                let back = end - direction * length;
+                let side = direction.rot90() * length / 2.0;
+
+                vec![start, end, back + side, back - side]
+            }
+        }
+    }
+}
+
+impl Line {
+    /// The polylines the line is drawn as, in the same coordinates as its points.
+    ///
+    /// Text is drawn on its own, this is the box around it. Lines with too few points for their
+    /// shape are drawn through their points.
+    pub fn paths(&self) -> Vec<Vec<Pos2>> {
+        if let Some(layout) = self.text_layout() {
+            let [a, b, c, d] = self.text_box(&layout);
+
+            return vec![vec![a, b, c, d, a]];
+        }
+
+        match (self.shape, &self.points[..]) {
+            (Shape::Line, [start, end, ..]) => vec![vec![*start, *end]],
+            (Shape::Rectangle, [a, b, c, d, ..]) => vec![vec![*a, *b, *c, *d, *a]],
+            (Shape::Ellipse, [center, x, y, ..]) => {
+                let (x, y) = (*x - *center, *y - *center);
+
+                let outline = (0..=ELLIPSE_SEGMENTS)
+                    .map(|i| {
+                        let (sin, cos) = (i as f32 / ELLIPSE_SEGMENTS as f32 * TAU).sin_cos();
+
+                        *center + x * cos + y * sin
+                    })
+                    .collect();
+
+                vec![outline]
+            }
+            (Shape::Arrow, [start, end, left, right, ..]) => {
+                vec![vec![*start, *end], vec![*left, *end, *right]]
+            }
+            _ => vec![self.points.clone()],
+        }
+    }
+}
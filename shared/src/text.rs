@@ -0,0 +1,80 @@
+use egui::{vec2, Color32, Pos2, Stroke, Vec2};
+
+use crate::{Line, Shape, DEFAULT_LAYER};
+
+/// Height of a row of text, relative to its font size.
+pub const ROW_HEIGHT: f32 = 1.2;
+
+/// Rough width of a character relative to the font size, for the box around text.
+const CHAR_WIDTH: f32 = 0.55;
+
+/// Where and how large the text of a [`Shape::Text`] line is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextLayout {
+    /// Top left corner of the first row.
+    pub pos: Pos2,
+    pub font_size: f32,
+    /// Angle of the rows in radians, clockwise in canvas coordinates.
+    pub angle: f32,
+}
+
+impl TextLayout {
+    /// Unit vector along the rows.
+    pub fn right(&self) -> Vec2 {
+        Vec2::angled(self.angle)
+    }
+
+    /// Unit vector from one row to the next.
+    pub fn down(&self) -> Vec2 {
+        -self.right().rot90()
+    }
+}
+
+impl Line {
+    /// Text with the top left corner at `pos`, in the color of the stroke.
+    ///
+    /// The second point is one font size below the first, so transforming the points moves, scales
+    /// and rotates the text.
+    pub fn new_text(pos: Pos2, font_size: f32, color: Color32, text: String) -> Self {
+        Self {
+            points: vec![pos, pos + vec2(0.0, font_size)],
+            stroke: Stroke::new(1.0, color),
+            shape: Shape::Text,
+            text,
+            layer: DEFAULT_LAYER,
+            cut_from: None,
+        }
+    }
+
+    /// `None` unless this is text.
+    pub fn text_layout(&self) -> Option<TextLayout> {
+        match (self.shape, &self.points[..]) {
+            (Shape::Text, [pos, below, ..]) => {
+                let down = *below - *pos;
+
+                Some(TextLayout {
+                    pos: *pos,
+                    font_size: down.length(),
+                    angle: down.rot90().angle(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Corners of the box the text roughly takes up, clockwise from the top left.
+    pub(crate) fn text_box(&self, layout: &TextLayout) -> [Pos2; 4] {
+        let columns = self.text.lines().map(|row| row.chars().count()).max();
+        let rows = self.text.lines().count().max(1);
+
+        let width = layout.right() * columns.unwrap_or(0) as f32 * CHAR_WIDTH * layout.font_size;
+        let height = layout.down() * rows as f32 * ROW_HEIGHT * layout.font_size;
+
+        [
+            layout.pos,
+            layout.pos + width,
+            layout.pos + width + height,
+            layout.pos + height,
+        ]
+    }
+}
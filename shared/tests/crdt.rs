@@ -0,0 +1,348 @@
+use egui::{pos2, vec2, Color32, Pos2, Stroke};
+use proptest::prelude::*;
+use shared::{Layer, Line, LineId, Lines, Operation, Timestamp, Transform};
+
+fn at(counter: u64, client: u64) -> Timestamp {
+    Timestamp { counter, client }
+}
+
+fn id(counter: u64) -> LineId {
+    LineId { counter, client: 1 }
+}
+
+fn line(points: &[Pos2]) -> Line {
+    let mut line = Line::new(Stroke::new(2.0, Color32::RED));
+    line.extend_from_slice(points);
+    line
+}
+
+fn drawn(lines: &Lines) -> Vec<LineId> {
+    lines.drawing_order().iter().map(|(id, _)| *id).collect()
+}
+
+fn apply_all(operations: &[Operation]) -> Lines {
+    let mut lines = Lines::default();
+
+    for operation in operations {
+        lines.apply(operation.clone());
+    }
+
+    lines
+}
+
+#[test]
+fn stale_repost_does_not_resurrect_removed_line() {
+    let add = Operation::AddLine {
+        id: id(1),
+        line: line(&[pos2(0.1, 0.1), pos2(0.2, 0.2)]),
+        timestamp: at(1, 1),
+    };
+    let remove = Operation::RemoveLines {
+        ids: vec![id(1)],
+        timestamp: at(2, 2),
+    };
+
+    assert!(apply_all(&[add.clone(), remove.clone(), add.clone()]).is_empty());
+    assert!(apply_all(&[remove, add]).is_empty());
+}
+
+#[test]
+fn newer_add_wins_over_older_remove() {
+    let remove = Operation::RemoveLines {
+        ids: vec![id(1)],
+        timestamp: at(2, 2),
+    };
+    let add = Operation::AddLine {
+        id: id(1),
+        line: line(&[pos2(0.1, 0.1)]),
+        timestamp: at(3, 1),
+    };
+
+    assert!(apply_all(&[add.clone(), remove.clone()]).contains_key(&id(1)));
+    assert!(apply_all(&[remove, add]).contains_key(&id(1)));
+}
+
+#[test]
+fn points_appended_before_the_line_arrives_are_kept() {
+    let points = [pos2(0.1, 0.1), pos2(0.2, 0.2), pos2(0.3, 0.3)];
+
+    let operations = [
+        Operation::AppendPoints {
+            id: id(1),
+            timestamp: at(1, 1),
+            stroke: line(&[]).stroke,
+            layer: 0,
+            offset: 2,
+            points: points[2..].to_vec(),
+        },
+        Operation::AddLine {
+            id: id(1),
+            line: line(&points[..2]),
+            timestamp: at(1, 1),
+        },
+    ];
+
+    assert_eq!(apply_all(&operations)[&id(1)].points, points.to_vec());
+}
+
+#[test]
+fn clear_keeps_concurrent_newer_lines() {
+    let lines = apply_all(&[
+        Operation::AddLine {
+            id: id(1),
+            line: line(&[pos2(0.1, 0.1)]),
+            timestamp: at(1, 1),
+        },
+        Operation::AddLine {
+            id: id(2),
+            line: line(&[pos2(0.1, 0.1)]),
+            timestamp: at(3, 2),
+        },
+        Operation::Clear {
+            timestamp: at(2, 3),
+        },
+        // Arrives late, but was drawn before the clear.
+        Operation::AddLine {
+            id: id(3),
+            line: line(&[pos2(0.1, 0.1)]),
+            timestamp: at(1, 2),
+        },
+    ]);
+
+    assert_eq!(lines.keys().copied().collect::<Vec<_>>(), vec![id(2)]);
+}
+
+#[test]
+fn transforms_apply_in_timestamp_order() {
+    let add = Operation::AddLine {
+        id: id(1),
+        line: line(&[pos2(1.0, 0.0)]),
+        timestamp: at(1, 1),
+    };
+    let scale = Operation::Transform {
+        ids: vec![id(1)],
+        transform: Transform::scale_about(pos2(0.0, 0.0), vec2(2.0, 2.0)),
+        timestamp: at(2, 1),
+    };
+    let translate = Operation::Transform {
+        ids: vec![id(1)],
+        transform: Transform::translate(vec2(1.0, 0.0)),
+        timestamp: at(2, 2),
+    };
+
+    let a = apply_all(&[add.clone(), scale.clone(), translate.clone()]);
+    let b = apply_all(&[translate, add, scale]);
+
+    assert_eq!(a, b);
+    assert_eq!(a[&id(1)][0], pos2(3.0, 0.0));
+}
+
+/// Something a client did, turned into operations by [`operations`].
+#[derive(Clone, Debug)]
+enum Edit {
+    Draw {
+        id: LineId,
+        points: Vec<Pos2>,
+        first_chunk: usize,
+        chunk: usize,
+        finished: bool,
+        layer: u64,
+    },
+    Remove {
+        ids: Vec<LineId>,
+    },
+    Clear,
+    Transform {
+        ids: Vec<LineId>,
+        dx: i8,
+        dy: i8,
+        scale: u8,
+    },
+    /// Few orders, so concurrent reorders often give layers the same one.
+    SetLayer {
+        id: u64,
+        order: i64,
+        hidden: bool,
+        locked: bool,
+    },
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+    let ids = prop::collection::vec((0..6u64).prop_map(id), 1..4);
+
+    prop_oneof![
+        4 => (
+            (0..6u64).prop_map(id),
+            prop::collection::vec((0..100u8, 0..100u8), 1..8),
+            1..4usize,
+            1..4usize,
+            any::<bool>(),
+            0..3u64,
+        )
+            .prop_map(|(id, points, first_chunk, chunk, finished, layer)| Edit::Draw {
+                id,
+                points: points
+                    .into_iter()
+                    .map(|(x, y)| pos2(x as f32, y as f32))
+                    .collect(),
+                first_chunk,
+                chunk,
+                finished,
+                layer,
+            }),
+        2 => ids.clone().prop_map(|ids| Edit::Remove { ids }),
+        1 => Just(Edit::Clear),
+        2 => (ids, any::<i8>(), any::<i8>(), 1..4u8)
+            .prop_map(|(ids, dx, dy, scale)| Edit::Transform { ids, dx, dy, scale }),
+        2 => (0..3u64, 0..2i64, any::<bool>(), any::<bool>())
+            .prop_map(|(id, order, hidden, locked)| Edit::SetLayer { id, order, hidden, locked }),
+    ]
+}
+
+/// Edits by up to four clients, each with a distinct timestamp, as the operations they send.
+fn operations() -> impl Strategy<Value = Vec<Operation>> {
+    prop::collection::vec((edit(), 1..10u64, 1..5u64), 1..16).prop_map(|edits| {
+        let mut operations = Vec::new();
+
+        for (index, (edit, counter, client)) in edits.into_iter().enumerate() {
+            // The index keeps timestamps unique even when counter and client repeat.
+            let timestamp = at(counter, client * 100 + index as u64);
+
+            match edit {
+                Edit::Draw {
+                    id,
+                    points,
+                    first_chunk,
+                    chunk,
+                    finished,
+                    layer,
+                } => {
+                    let first_chunk = first_chunk.min(points.len());
+
+                    let line = |points: &[Pos2]| Line {
+                        layer,
+                        ..line(points)
+                    };
+
+                    operations.push(Operation::AddLine {
+                        id,
+                        line: line(&points[..first_chunk]),
+                        timestamp,
+                    });
+
+                    for offset in (first_chunk..points.len()).step_by(chunk) {
+                        let end = (offset + chunk).min(points.len());
+
+                        operations.push(Operation::AppendPoints {
+                            id,
+                            timestamp,
+                            stroke: line(&[]).stroke,
+                            layer,
+                            offset,
+                            points: points[offset..end].to_vec(),
+                        });
+                    }
+
+                    if finished {
+                        operations.push(Operation::AddLine {
+                            id,
+                            line: line(&points),
+                            timestamp,
+                        });
+                    }
+                }
+                Edit::Remove { ids } => operations.push(Operation::RemoveLines { ids, timestamp }),
+                Edit::Clear => operations.push(Operation::Clear { timestamp }),
+                Edit::Transform { ids, dx, dy, scale } => operations.push(Operation::Transform {
+                    ids,
+                    transform: Transform::scale_about(pos2(50.0, 50.0), vec2(scale as f32, 1.0))
+                        .then(Transform::translate(vec2(dx as f32, dy as f32))),
+                    timestamp,
+                }),
+                Edit::SetLayer {
+                    id,
+                    order,
+                    hidden,
+                    locked,
+                } => operations.push(Operation::SetLayer {
+                    id,
+                    layer: Layer {
+                        hidden,
+                        locked,
+                        ..Layer::new(format!("Layer {}", id), order)
+                    },
+                    timestamp,
+                }),
+            }
+        }
+
+        operations
+    })
+}
+
+/// The same operations in two independent orders.
+fn shuffled_twice() -> impl Strategy<Value = (Vec<Operation>, Vec<Operation>)> {
+    operations().prop_flat_map(|operations| {
+        (
+            Just(operations.clone()).prop_shuffle(),
+            Just(operations).prop_shuffle(),
+        )
+    })
+}
+
+proptest! {
+    #[test]
+    fn operations_converge_in_any_order((a, b) in shuffled_twice()) {
+        let duplicated: Vec<Operation> = b
+            .iter()
+            .flat_map(|operation| [operation.clone(), operation.clone()])
+            .collect();
+
+        let lines = apply_all(&a);
+
+        prop_assert_eq!(&lines, &apply_all(&b));
+        prop_assert_eq!(&lines, &apply_all(&duplicated));
+        prop_assert_eq!(lines.layers(), apply_all(&b).layers());
+        prop_assert_eq!(drawn(&lines), drawn(&apply_all(&b)));
+    }
+
+    #[test]
+    fn merge_is_commutative_associative_and_idempotent(
+        (operations, replicas) in operations().prop_flat_map(|operations| {
+            let len = operations.len();
+            (Just(operations), prop::collection::vec(0..3usize, len))
+        })
+    ) {
+        let mut parts = [Lines::default(), Lines::default(), Lines::default()];
+
+        for (operation, replica) in operations.iter().zip(replicas) {
+            parts[replica].apply(operation.clone());
+        }
+
+        let [a, b, c] = parts;
+
+        let merged = |first: &Lines, rest: &[&Lines]| {
+            let mut lines = first.clone();
+            for other in rest {
+                lines.merge((*other).clone());
+            }
+            lines
+        };
+
+        let abc = merged(&a, &[&b, &c]);
+
+        prop_assert_eq!(&abc, &merged(&c, &[&b, &a]));
+        prop_assert_eq!(&abc, &merged(&b, &[&merged(&c, &[&a])]));
+        prop_assert_eq!(&abc, &merged(&abc, &[&a, &b, &c]));
+        prop_assert_eq!(&abc, &apply_all(&operations));
+    }
+
+    #[test]
+    fn serialized_state_merges_the_same(operations in operations()) {
+        let lines = apply_all(&operations);
+
+        let parsed: Lines = lines.to_string().parse().unwrap();
+
+        prop_assert_eq!(&parsed, &lines);
+    }
+}
@@ -0,0 +1,35 @@
+use egui::{pos2, Rect};
+use shared::{Cursor, Envelope, Event, Operation, Timestamp};
+
+#[test]
+fn envelopes_still_decode_as_operations() {
+    let envelope = Envelope::new(vec![Operation::Clear {
+        timestamp: Timestamp::default(),
+    }]);
+
+    let event = serde_json::from_str(&serde_json::to_string(&envelope).unwrap()).unwrap();
+
+    assert!(matches!(event, Event::Operations(envelope) if envelope.operations.len() == 1));
+}
+
+#[test]
+fn cursors_roundtrip_in_normalized_coordinates() {
+    let canvas = Rect::from_min_max(pos2(100.0, 50.0), pos2(300.0, 250.0));
+
+    let mut cursor = Cursor {
+        session: 3,
+        pos: Some(pos2(200.0, 100.0)),
+    };
+
+    cursor.from_canvas(&canvas);
+    assert_eq!(cursor.pos, Some(pos2(0.5, 0.25)));
+
+    let json = serde_json::to_string(&Event::Cursor { cursor }).unwrap();
+
+    let Event::Cursor { mut cursor } = serde_json::from_str(&json).unwrap() else {
+        panic!("expected a cursor in {}", json);
+    };
+
+    cursor.to_canvas(&canvas);
+    assert_eq!(cursor.pos, Some(pos2(200.0, 100.0)));
+}
@@ -0,0 +1,61 @@
+use egui::{pos2, Color32, Pos2, Stroke};
+use shared::{Line, Shape};
+
+fn line(points: &[Pos2]) -> Line {
+    let mut line = Line::new(Stroke::new(2.0, Color32::BLACK));
+    line.extend_from_slice(points);
+    line
+}
+
+#[test]
+fn segments_are_hit_between_their_points() {
+    let line = line(&[pos2(0.0, 0.0), pos2(100.0, 0.0)]);
+
+    assert_eq!(line.distance(pos2(50.0, 5.0)), 5.0);
+    assert_eq!(line.erase(pos2(50.0, 20.0), 10.0), None);
+}
+
+#[test]
+fn erasing_the_middle_splits_a_line() {
+    let line = line(&[pos2(0.0, 0.0), pos2(50.0, 0.0), pos2(100.0, 0.0)]);
+
+    let pieces = line.erase(pos2(50.0, 0.0), 10.0).unwrap();
+
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(pieces[0][..], [pos2(0.0, 0.0), pos2(40.0, 0.0)]);
+    assert_eq!(pieces[1][..], [pos2(60.0, 0.0), pos2(100.0, 0.0)]);
+    assert_eq!(pieces[0].stroke, line.stroke);
+
+    // The ends of the pieces touch the eraser, which must not cut them again.
+    assert!(pieces
+        .iter()
+        .all(|piece| piece.erase(pos2(50.0, 0.0), 10.0).is_none()));
+}
+
+#[test]
+fn erasing_an_end_or_everything() {
+    let line = line(&[pos2(0.0, 0.0), pos2(100.0, 0.0)]);
+
+    let pieces = line.erase(pos2(100.0, 0.0), 10.0).unwrap();
+
+    assert_eq!(pieces.len(), 1);
+    assert_eq!(pieces[0][..], [pos2(0.0, 0.0), pos2(90.0, 0.0)]);
+
+    assert_eq!(line.erase(pos2(50.0, 0.0), 100.0), Some(Vec::new()));
+}
+
+#[test]
+fn shapes_are_cut_along_their_outline_and_text_is_erased_whole() {
+    let mut rectangle = Line::new(Stroke::new(2.0, Color32::BLACK));
+    rectangle.shape = Shape::Rectangle;
+    rectangle.points = Shape::Rectangle.points(pos2(0.0, 0.0), pos2(100.0, 100.0), 2.0);
+
+    let pieces = rectangle.erase(pos2(50.0, 0.0), 10.0).unwrap();
+
+    assert_eq!(pieces.len(), 2);
+    assert!(pieces.iter().all(|piece| piece.shape == Shape::Freehand));
+
+    let text = Line::new_text(pos2(0.0, 0.0), 20.0, Color32::BLACK, "Label".to_string());
+
+    assert_eq!(text.erase(pos2(10.0, 10.0), 1.0), Some(Vec::new()));
+}
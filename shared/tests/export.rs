@@ -0,0 +1,57 @@
+use egui::{pos2, vec2, Color32, Stroke};
+use shared::{
+    export_svg, png_size, Line, Lines, Operation, Timestamp, EXPORT_SIZE_WITHOUT_BACKGROUND,
+};
+
+const BACKGROUND: &[u8] = include_bytes!("../../assets/icon-256.png");
+
+fn lines() -> Lines {
+    let mut line = Line::new(Stroke::new(4.0, Color32::from_rgb(0x12, 0x34, 0x56)));
+    line.extend([pos2(0.0, 0.0), pos2(0.5, 1.0)]);
+
+    let mut lines = Lines::default();
+    lines.apply(Operation::AddLine {
+        id: Timestamp::default().id(),
+        line,
+        timestamp: Timestamp::default(),
+    });
+    lines
+}
+
+#[test]
+fn svg_scales_lines_to_the_background() {
+    assert_eq!(png_size(BACKGROUND), Some(vec2(256.0, 256.0)));
+
+    let svg = export_svg(&lines(), Some(BACKGROUND));
+
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="256" height="256""#));
+    assert!(svg.contains("data:image/png;base64,"));
+    assert!(svg.contains(r##"points="0,0 128,256" fill="none" stroke="#123456""##));
+    assert!(svg.contains(r#"stroke-width="4""#));
+    assert!(svg.trim_end().ends_with("</svg>"));
+}
+
+#[test]
+fn svg_without_background() {
+    let svg = export_svg(&lines(), None);
+
+    assert!(svg.contains(&format!(r#"width="{}""#, EXPORT_SIZE_WITHOUT_BACKGROUND.x)));
+    assert!(!svg.contains("<image"));
+    assert_eq!(png_size(b"not a png"), None);
+}
+
+#[test]
+fn svg_exports_text_rows() {
+    let mut lines = Lines::default();
+    lines.apply(Operation::AddLine {
+        id: Timestamp::default().id(),
+        line: Line::new_text(pos2(0.5, 0.25), 0.125, Color32::RED, "a < b\nc".to_string()),
+        timestamp: Timestamp::default(),
+    });
+
+    let svg = export_svg(&lines, None);
+
+    assert!(svg.contains(r#"<text transform="translate(512,256)" font-size="128""#));
+    assert!(svg.contains(r#"<tspan x="0" y="0">a &lt; b</tspan>"#));
+    assert!(svg.contains(r#"<tspan x="0" y="153.6">c</tspan>"#));
+}
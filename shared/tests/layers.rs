@@ -0,0 +1,155 @@
+use egui::{pos2, Color32, Stroke};
+use shared::{Layer, Line, LineId, Lines, Operation, Timestamp, DEFAULT_LAYER};
+
+fn at(counter: u64, client: u64) -> Timestamp {
+    Timestamp { counter, client }
+}
+
+fn line_on(layer: u64) -> Line {
+    let mut line = Line::new(Stroke::new(2.0, Color32::RED));
+    line.extend([pos2(0.1, 0.1), pos2(0.2, 0.2)]);
+    line.layer = layer;
+    line
+}
+
+fn add(layer: u64, timestamp: Timestamp) -> Operation {
+    Operation::AddLine {
+        id: timestamp.id(),
+        line: line_on(layer),
+        timestamp,
+    }
+}
+
+fn set_layer(id: u64, layer: Layer, timestamp: Timestamp) -> Operation {
+    Operation::SetLayer {
+        id,
+        layer,
+        timestamp,
+    }
+}
+
+fn drawn(lines: &Lines) -> Vec<LineId> {
+    lines.drawing_order().iter().map(|(id, _)| *id).collect()
+}
+
+#[test]
+fn lines_are_drawn_by_layer_order_then_by_when_they_were_created() {
+    let (a, b, c) = (at(2, 1), at(3, 1), at(4, 2));
+
+    let mut lines = Lines::default();
+
+    lines.apply(set_layer(7, Layer::new("Top".to_string(), 1), at(1, 1)));
+    lines.apply(add(DEFAULT_LAYER, c));
+    lines.apply(add(7, a));
+    lines.apply(add(DEFAULT_LAYER, b));
+
+    assert_eq!(drawn(&lines), vec![b.id(), c.id(), a.id()]);
+
+    lines.apply(set_layer(7, Layer::new("Top".to_string(), -1), at(5, 2)));
+
+    assert_eq!(drawn(&lines), vec![a.id(), b.id(), c.id()]);
+
+    let hidden = Layer {
+        hidden: true,
+        ..Layer::default()
+    };
+
+    lines.apply(set_layer(DEFAULT_LAYER, hidden, at(6, 1)));
+
+    assert_eq!(drawn(&lines), vec![a.id()]);
+    assert!(lines.is_editable(&a.id()));
+    assert!(!lines.is_editable(&b.id()));
+    assert_eq!(lines.len(), 3);
+}
+
+#[test]
+fn ids_keep_lines_in_creation_order() {
+    let older = at(9, u64::MAX);
+    let newer = at(10, 1);
+
+    assert!(older.id() < newer.id());
+    assert_ne!(at(10, 2).id(), newer.id());
+
+    let mut lines = Lines::default();
+
+    lines.apply(add(DEFAULT_LAYER, newer));
+    lines.apply(add(DEFAULT_LAYER, older));
+
+    // Changing the older line later does not move it on top.
+    lines.apply(Operation::AddLine {
+        id: older.id(),
+        line: line_on(DEFAULT_LAYER),
+        timestamp: at(11, 1),
+    });
+
+    assert_eq!(drawn(&lines), vec![older.id(), newer.id()]);
+    assert_eq!(lines.keys().copied().collect::<Vec<_>>(), drawn(&lines));
+}
+
+#[test]
+fn pieces_left_by_the_eraser_are_drawn_in_place_of_their_line() {
+    let (cut, above) = (at(2, 1), at(3, 2));
+    let (piece, piece_of_piece) = (at(4, 1), at(5, 1));
+
+    let mut lines = Lines::default();
+
+    lines.apply(add(DEFAULT_LAYER, cut));
+    lines.apply(add(DEFAULT_LAYER, above));
+    lines.apply(Operation::RemoveLines {
+        ids: vec![cut.id()],
+        timestamp: piece,
+    });
+    lines.apply(Operation::AddLine {
+        id: piece.id(),
+        line: Line {
+            cut_from: Some(cut.id()),
+            ..line_on(DEFAULT_LAYER)
+        },
+        timestamp: piece,
+    });
+    lines.apply(Operation::AddLine {
+        id: piece_of_piece.id(),
+        line: Line {
+            cut_from: Some(cut.id()),
+            ..line_on(DEFAULT_LAYER)
+        },
+        timestamp: piece_of_piece,
+    });
+
+    assert_eq!(
+        drawn(&lines),
+        vec![piece.id(), piece_of_piece.id(), above.id()]
+    );
+}
+
+#[test]
+fn newest_version_of_a_layer_wins_and_survives_a_clear() {
+    let renamed = set_layer(1, Layer::new("Renamed".to_string(), 1), at(3, 2));
+    let added = set_layer(1, Layer::new("Sketch".to_string(), 1), at(2, 1));
+
+    let mut lines = Lines::default();
+    lines.apply(renamed.clone());
+    lines.apply(added.clone());
+
+    let mut other = Lines::default();
+    other.apply(added);
+    other.apply(renamed);
+    other.apply(Operation::Clear {
+        timestamp: at(4, 1),
+    });
+
+    lines.merge(other.subset([]));
+
+    let names: Vec<String> = lines
+        .layers()
+        .into_iter()
+        .map(|(_, layer)| layer.name)
+        .collect();
+
+    assert_eq!(names, vec!["Layer 1", "Renamed"]);
+    assert_eq!(lines.latest_timestamp(), at(4, 1));
+
+    let json = lines.to_string();
+
+    assert_eq!(json.parse::<Lines>().unwrap(), lines);
+}
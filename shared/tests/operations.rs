@@ -0,0 +1,194 @@
+use egui::{pos2, vec2, Rect, Stroke};
+use shared::{Delta, Envelope, Line, LineId, Lines, Operation, Timestamp, Transform};
+
+fn line(points: &[(f32, f32)]) -> Line {
+    let mut line = Line::new(Stroke::new(2.0, egui::Color32::RED));
+    line.extend(points.iter().map(|(x, y)| pos2(*x, *y)));
+    line
+}
+
+fn at(counter: u64) -> Timestamp {
+    Timestamp { counter, client: 1 }
+}
+
+fn id(counter: u64) -> LineId {
+    at(counter).id()
+}
+
+fn assert_close(a: egui::Pos2, b: egui::Pos2) {
+    assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn apply_add_append_remove_clear() {
+    let mut lines = Lines::default();
+
+    lines.apply(Operation::AddLine {
+        id: id(1),
+        line: line(&[(0.1, 0.1)]),
+        timestamp: at(1),
+    });
+    lines.apply(Operation::AddLine {
+        id: id(2),
+        line: line(&[(0.5, 0.5)]),
+        timestamp: at(2),
+    });
+    lines.apply(Operation::AppendPoints {
+        id: id(1),
+        timestamp: at(1),
+        stroke: lines[&id(1)].stroke,
+        layer: 0,
+        offset: 1,
+        points: vec![pos2(0.2, 0.2)],
+    });
+
+    assert_eq!(lines[&id(1)].points, vec![pos2(0.1, 0.1), pos2(0.2, 0.2)]);
+
+    lines.apply(Operation::RemoveLines {
+        ids: vec![id(2)],
+        timestamp: at(3),
+    });
+
+    assert!(!lines.contains_key(&id(2)));
+
+    // Appending to a removed line does not bring it back.
+    lines.apply(Operation::AppendPoints {
+        id: id(2),
+        timestamp: at(2),
+        stroke: Stroke::new(2.0, egui::Color32::RED),
+        layer: 0,
+        offset: 1,
+        points: vec![pos2(0.6, 0.6)],
+    });
+
+    assert!(!lines.contains_key(&id(2)));
+
+    lines.apply(Operation::Clear { timestamp: at(4) });
+
+    assert!(lines.is_empty());
+}
+
+#[test]
+fn apply_transform_only_touches_given_lines() {
+    let mut lines = Lines::default();
+
+    lines.apply(Operation::AddLine {
+        id: id(1),
+        line: line(&[(0.1, 0.1)]),
+        timestamp: at(1),
+    });
+    lines.apply(Operation::AddLine {
+        id: id(2),
+        line: line(&[(0.1, 0.1)]),
+        timestamp: at(2),
+    });
+    lines.apply(Operation::Transform {
+        ids: vec![id(1)],
+        transform: Transform::translate(vec2(0.2, 0.3)),
+        timestamp: at(3),
+    });
+
+    assert_close(lines[&id(1)][0], pos2(0.3, 0.4));
+    assert_close(lines[&id(2)][0], pos2(0.1, 0.1));
+}
+
+#[test]
+fn transform_inverse_and_composition() {
+    let transform = Transform::rotate_about(pos2(1.0, 1.0), 0.5)
+        .then(Transform::scale_about(pos2(2.0, 0.0), vec2(2.0, 0.5)))
+        .then(Transform::translate(vec2(-3.0, 4.0)));
+
+    let pos = pos2(0.25, -1.5);
+
+    assert_close(
+        transform.inverse().unwrap().apply(transform.apply(pos)),
+        pos,
+    );
+
+    assert_close(
+        Transform::rotate_about(pos2(1.0, 1.0), std::f32::consts::FRAC_PI_2).apply(pos2(2.0, 1.0)),
+        pos2(1.0, 2.0),
+    );
+
+    assert!(Transform::scale_about(pos2(0.0, 0.0), vec2(0.0, 1.0))
+        .inverse()
+        .is_none());
+}
+
+#[test]
+fn canvas_and_normalized_operations_agree() {
+    let canvas_rect = Rect::from_min_size(pos2(10.0, 20.0), vec2(200.0, 100.0));
+
+    let mut on_canvas = Lines::default();
+    on_canvas.apply(Operation::AddLine {
+        id: id(1),
+        line: line(&[(30.0, 40.0), (110.0, 70.0)]),
+        timestamp: at(1),
+    });
+
+    let mut normalized = on_canvas.clone();
+    normalized.from_canvas(&canvas_rect);
+
+    let transform = Operation::Transform {
+        ids: vec![id(1)],
+        transform: Transform::rotate_about(pos2(50.0, 50.0), 1.0),
+        timestamp: at(2),
+    };
+
+    let mut normalized_transform = transform.clone();
+    normalized_transform.from_canvas(&canvas_rect);
+
+    on_canvas.apply(transform);
+    normalized.apply(normalized_transform);
+    normalized.to_canvas(&canvas_rect);
+
+    for (a, b) in on_canvas[&id(1)].iter().zip(normalized[&id(1)].iter()) {
+        assert!((*a - *b).length() < 1e-3, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn envelope_roundtrip_and_version() {
+    let envelope = Envelope::new(vec![
+        Operation::AddLine {
+            id: LineId {
+                counter: u64::MAX,
+                client: u64::MAX,
+            },
+            line: line(&[(0.1, 0.2)]),
+            timestamp: at(1),
+        },
+        Operation::RemoveLines {
+            ids: vec![id(3), id(4)],
+            timestamp: at(2),
+        },
+        Operation::Clear { timestamp: at(3) },
+    ]);
+
+    let json = serde_json::to_string(&envelope).unwrap();
+    let parsed: Envelope = serde_json::from_str(&json).unwrap();
+
+    assert!(parsed.is_supported());
+    assert_eq!(parsed.operations, envelope.operations);
+
+    let future: Envelope = serde_json::from_str(r#"{"version":999,"operations":[]}"#).unwrap();
+
+    assert!(!future.is_supported());
+}
+
+#[test]
+fn line_ids_are_sent_as_counter_and_client() {
+    assert_eq!(serde_json::to_string(&id(3)).unwrap(), r#""3.1""#);
+    assert_eq!(serde_json::from_str::<LineId>(r#""3.1""#).unwrap(), id(3));
+    assert!(serde_json::from_str::<LineId>("12345").is_err());
+    assert!("3.".parse::<LineId>().is_err());
+}
+
+#[test]
+fn malformed_replies_are_errors() {
+    assert!(r#"{"revision": 1"#.parse::<Delta>().is_err());
+    assert!("[1, 2]".parse::<Lines>().is_err());
+
+    let delta = serde_json::to_string(&Delta::default()).unwrap();
+    assert!(delta.parse::<Delta>().is_ok());
+}
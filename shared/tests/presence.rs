@@ -0,0 +1,20 @@
+use std::collections::HashSet;
+
+use shared::{display_name, participant_color, MAX_NAME_LEN};
+
+#[test]
+fn display_names_are_trimmed_and_cut() {
+    assert_eq!(display_name("  Ada "), "Ada");
+    assert_eq!(display_name("   "), "Anonymous");
+    assert_eq!(
+        display_name(&"é".repeat(MAX_NAME_LEN + 5)).chars().count(),
+        MAX_NAME_LEN
+    );
+}
+
+#[test]
+fn participant_colors_differ() {
+    let colors: HashSet<_> = (0..100).map(participant_color).collect();
+
+    assert_eq!(colors.len(), 100);
+}
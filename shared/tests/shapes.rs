@@ -0,0 +1,70 @@
+use egui::{pos2, Color32, Pos2, Stroke};
+use shared::{Line, Shape, Transform};
+
+fn shape(shape: Shape, start: Pos2, end: Pos2) -> Line {
+    let mut line = Line::new(Stroke::new(2.0, Color32::BLACK));
+    line.shape = shape;
+    line.points = shape.points(start, end, 2.0);
+    line
+}
+
+#[test]
+fn freehand_lines_are_stored_like_before_shapes() {
+    let line = shape(Shape::Freehand, pos2(0.0, 0.0), pos2(1.0, 1.0));
+
+    let json = serde_json::to_string(&line).unwrap();
+
+    assert!(!json.contains("shape"));
+    assert_eq!(serde_json::from_str::<Line>(&json).unwrap(), line);
+
+    let rectangle = shape(Shape::Rectangle, pos2(0.0, 0.0), pos2(1.0, 1.0));
+
+    let json = serde_json::to_string(&rectangle).unwrap();
+
+    assert_eq!(serde_json::from_str::<Line>(&json).unwrap(), rectangle);
+}
+
+#[test]
+fn shapes_are_drawn_as_their_outline() {
+    let rectangle = shape(Shape::Rectangle, pos2(10.0, 20.0), pos2(0.0, 0.0));
+
+    assert_eq!(
+        rectangle.paths(),
+        [vec![
+            pos2(0.0, 0.0),
+            pos2(10.0, 0.0),
+            pos2(10.0, 20.0),
+            pos2(0.0, 20.0),
+            pos2(0.0, 0.0)
+        ]]
+    );
+
+    let arrow = shape(Shape::Arrow, pos2(0.0, 0.0), pos2(100.0, 0.0));
+
+    assert_eq!(arrow.paths().len(), 2);
+    assert_eq!(arrow.paths()[1][1], pos2(100.0, 0.0));
+
+    let ellipse = shape(Shape::Ellipse, pos2(0.0, 0.0), pos2(20.0, 10.0));
+
+    for pos in &ellipse.paths()[0] {
+        let (x, y) = ((pos.x - 10.0) / 10.0, (pos.y - 5.0) / 5.0);
+
+        assert!((x * x + y * y - 1.0).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn transformed_shapes_keep_their_form() {
+    let mut ellipse = shape(Shape::Ellipse, pos2(-2.0, -1.0), pos2(2.0, 1.0));
+
+    let rotate = Transform::rotate_about(Pos2::ZERO, std::f32::consts::FRAC_PI_2);
+
+    for pos in ellipse.points.iter_mut() {
+        *pos = rotate.apply(*pos);
+    }
+
+    // Turned by a quarter, so it is now higher than wide.
+    for pos in &ellipse.paths()[0] {
+        assert!((pos.x * pos.x + pos.y * pos.y / 4.0 - 1.0).abs() < 1e-4);
+    }
+}